@@ -27,6 +27,34 @@ function anchorSighashGlobal(name: string): Buffer {
   return h.subarray(0, 8);
 }
 
+// Borsh layout of ReadPriceArgs: feed_id ([u8; 32]), feed_id_hex (u32 len + utf8), max_age_secs (u64),
+// max_conf_ratio_bps (u64), max_abs_conf (u64), min_publishers (u32), max_deviation_bps (u64),
+// verbose (bool), reject_negative (bool), max_publish_time (i64). The raw id is sent and the hex left
+// empty, so the program skips the hex parse. min_publishers must be 0, max_deviation_bps is 0 since no
+// cache account is passed, verbose is on so the price shows up in the logs printed below, negative
+// prices are rejected since ETH/USD is an asset feed, and max_publish_time is 0 (no upper bound).
+function encodeReadPriceArgs(
+  feedIdHex: string,
+  maxAgeSecs: bigint,
+  maxConfRatioBps: bigint,
+  maxAbsConf: bigint
+): Buffer {
+  const feedId = Buffer.from(feedIdHex.replace(/^0x/, ""), "hex");
+  if (feedId.length !== 32) throw new Error(`Bad feed id: ${feedIdHex}`);
+  const buf = Buffer.alloc(32 + 4 + 8 + 8 + 8 + 4 + 8 + 1 + 1 + 8);
+  let offset = feedId.copy(buf, 0);
+  offset = buf.writeUInt32LE(0, offset);
+  offset = buf.writeBigUInt64LE(maxAgeSecs, offset);
+  offset = buf.writeBigUInt64LE(maxConfRatioBps, offset);
+  offset = buf.writeBigUInt64LE(maxAbsConf, offset);
+  offset = buf.writeUInt32LE(0, offset);
+  offset = buf.writeBigUInt64LE(0n, offset);
+  offset = buf.writeUInt8(1, offset);
+  offset = buf.writeUInt8(1, offset);
+  buf.writeBigInt64LE(0n, offset);
+  return buf;
+}
+
 // Hermes v2 (with legacy fallback) → return base64 updates (string[])
 async function fetchPythUpdates(feedIdHex: string): Promise<string[]> {
   const base = process.env.HERMES_URL ?? "https://hermes.pyth.network";
@@ -119,6 +147,9 @@ async function main() {
   const rpc = requireEnv("SOLANA_RPC_URL");
   const programId = new PublicKey(requireEnv("PROGRAM_ID"));
   const feedIdHex = requireEnv("PYTH_FEED_ID_HEX");
+  const maxAgeSecs = BigInt(process.env.MAX_AGE_SECS ?? "60");
+  const maxConfRatioBps = BigInt(process.env.MAX_CONF_RATIO_BPS ?? "200");
+  const maxAbsConf = BigInt(process.env.MAX_ABS_CONF ?? "0"); // 0 = no absolute cap
   const keypath =
     process.env.PAYER_KEYPAIR ?? path.join(process.env.HOME || "", ".config/solana/id.json");
 
@@ -128,6 +159,8 @@ async function main() {
   );
   const connection = new Connection(rpc, "confirmed");
   const provider = new AnchorProvider(connection, new Wallet(payer), {});
+  const [configPk] = PublicKey.findProgramAddressSync([Buffer.from("config")], programId);
+  const [allowlistPk] = PublicKey.findProgramAddressSync([Buffer.from("allowlist")], programId);
 
   // --- 1) fetch signed update (Hermes over HTTP) ---
   const priceUpdateData = await fetchPythUpdates(feedIdHex);
@@ -145,12 +178,23 @@ async function main() {
     async (getPriceUpdateAccount: (feedId: string) => PublicKey) => {
       const priceUpdatePk = getPriceUpdateAccount(feedIdHex);
 
-      // read_price has no args → data = 8-byte discriminator only
-      const data = anchorSighashGlobal("read_price");
+      // data = 8-byte discriminator + Borsh-encoded ReadPriceArgs
+      const data = Buffer.concat([
+        anchorSighashGlobal("read_price"),
+        encodeReadPriceArgs(feedIdHex, maxAgeSecs, maxConfRatioBps, maxAbsConf),
+      ]);
 
       const ix = new TransactionInstruction({
         programId,
-        keys: [{ pubkey: priceUpdatePk, isSigner: false, isWritable: false }],
+        keys: [
+          { pubkey: priceUpdatePk, isSigner: false, isWritable: false },
+          // config PDA: once an admin has created it, its thresholds cap the ones in the args
+          { pubkey: configPk, isSigner: false, isWritable: false },
+          // allowlist PDA: enforced onchain once an admin has created it
+          { pubkey: allowlistPk, isSigner: false, isWritable: false },
+          // optional price cache for the deviation check: None
+          { pubkey: programId, isSigner: false, isWritable: false },
+        ],
         data,
       });
 
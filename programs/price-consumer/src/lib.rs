@@ -0,0 +1,100 @@
+#![allow(deprecated)] // anchor 0.31 #[program] codegen still calls AccountInfo::realloc
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::get_return_data;
+use pyth_demo::cpi::accounts::{ ReadPrice, ReadPriceInto };
+use pyth_demo::program::PythDemo;
+use pyth_demo::{ PriceScratch, ReadPriceArgs, ValidatedPrice };
+
+declare_id!("4NBYoTwJnWGR8NsybeeXi8hPJTbJyNamaVLUNzMcMEcw"); // replace with your program ID
+
+#[program]
+pub mod price_consumer {
+    use super::*;
+
+    pub fn consume_price(ctx: Context<ConsumePrice>, args: ReadPriceArgs) -> Result<()> {
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.pyth_demo_program.to_account_info(),
+            ReadPrice {
+                price_update: ctx.accounts.price_update.to_account_info(),
+                config: ctx.accounts.config.to_account_info(),
+                allowlist: ctx.accounts.allowlist.to_account_info(),
+                price_cache: None,
+            },
+        );
+
+        // pyth-demo validated the price; read what it returned, checking the layout version
+        pyth_demo::cpi::read_price(cpi_ctx, args)?;
+        let (program_id, data) = get_return_data().unwrap_or_default();
+        require_keys_eq!(program_id, pyth_demo::ID, anchor_lang::error::ErrorCode::InvalidProgramId);
+        let p: ValidatedPrice = pyth_demo::decode_validated_price(&data)?;
+
+        msg!(
+            "returned price={}, conf={}, exponent={}, t={}",
+            p.price,
+            p.conf,
+            p.exponent,
+            p.publish_time
+        );
+
+        Ok(())
+    }
+
+    pub fn consume_price_via_scratch(ctx: Context<ConsumePriceViaScratch>, args: ReadPriceArgs) -> Result<()> {
+        let signer_seeds: &[&[&[u8]]] = &[&[b"reader", &[ctx.bumps.reader]]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.pyth_demo_program.to_account_info(),
+            ReadPriceInto {
+                price_update: ctx.accounts.price_update.to_account_info(),
+                config: ctx.accounts.config.to_account_info(),
+                allowlist: ctx.accounts.allowlist.to_account_info(),
+                scratch: ctx.accounts.scratch.to_account_info(),
+                authority: ctx.accounts.reader.to_account_info(),
+            },
+            signer_seeds,
+        );
+        pyth_demo::cpi::read_price_into(cpi_ctx, args)?;
+
+        // The CPI wrote the account behind our deserialized copy, so load it again
+        ctx.accounts.scratch.reload()?;
+        let p = &ctx.accounts.scratch.price;
+
+        msg!(
+            "scratch price={}, conf={}, exponent={}, t={}",
+            p.price,
+            p.conf,
+            p.exponent,
+            p.publish_time
+        );
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct ConsumePrice<'info> {
+    /// CHECK: validated by pyth-demo
+    pub price_update: UncheckedAccount<'info>,
+    /// CHECK: validated by pyth-demo
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: validated by pyth-demo
+    pub allowlist: UncheckedAccount<'info>,
+    pub pyth_demo_program: Program<'info, PythDemo>,
+}
+
+#[derive(Accounts)]
+pub struct ConsumePriceViaScratch<'info> {
+    /// CHECK: validated by pyth-demo
+    pub price_update: UncheckedAccount<'info>,
+    /// CHECK: validated by pyth-demo
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: validated by pyth-demo
+    pub allowlist: UncheckedAccount<'info>,
+    /// Created for `reader` with pyth-demo's `init_scratch`
+    #[account(mut)]
+    pub scratch: Account<'info, PriceScratch>,
+    /// CHECK: signs for the scratch account; holds no data
+    #[account(seeds = [b"reader"], bump)]
+    pub reader: UncheckedAccount<'info>,
+    pub pyth_demo_program: Program<'info, PythDemo>,
+}
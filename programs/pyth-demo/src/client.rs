@@ -0,0 +1,144 @@
+//! Off-chain helpers for calling `read_price` over RPC and rendering what it returns. Enabled by the
+//! `client` feature.
+
+use std::fmt;
+
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use pyth_solana_receiver_sdk::price_update::{get_feed_id_from_hex, PriceUpdateV2};
+use pyth_solana_receiver_sdk::PYTH_PUSH_ORACLE_ID;
+use solana_client::client_error::ClientError as RpcError;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::transaction::Transaction;
+
+use crate::ReadPriceArgs;
+
+/// Shard the sponsored price feed accounts are published on.
+pub const DEFAULT_SHARD_ID: u16 = 0;
+
+/// Largest exponent magnitude `format_price` renders: every digit of an i128 plus
+/// `math::MAX_TARGET_DECIMALS`, far past anything Pyth publishes.
+pub const MAX_FORMAT_EXPONENT: u32 = 39 + crate::math::MAX_TARGET_DECIMALS;
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// Not a 32-byte hex feed id
+    BadFeedId(String),
+    /// The derived account is missing or is not a `PriceUpdateV2`
+    NotAPriceUpdate(Pubkey),
+    /// Magnitude past `MAX_FORMAT_EXPONENT`, which would render megabytes of zeros
+    ExponentOutOfRange(i32),
+    Rpc(Box<RpcError>),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::BadFeedId(hex) => write!(f, "bad feed id: {hex}"),
+            ClientError::NotAPriceUpdate(key) => write!(f, "{key} is not a PriceUpdateV2 account"),
+            ClientError::ExponentOutOfRange(exponent) => {
+                write!(f, "exponent {exponent} is outside ±{MAX_FORMAT_EXPONENT}")
+            }
+            ClientError::Rpc(e) => write!(f, "rpc: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<RpcError> for ClientError {
+    fn from(e: RpcError) -> Self {
+        ClientError::Rpc(Box::new(e))
+    }
+}
+
+pub type Result<T> = core::result::Result<T, ClientError>;
+
+/// Address of the sponsored price feed account for `feed_id` on `shard_id`.
+pub fn price_feed_address(shard_id: u16, feed_id: &[u8; 32]) -> Pubkey {
+    Pubkey::find_program_address(&[&shard_id.to_le_bytes(), feed_id], &PYTH_PUSH_ORACLE_ID).0
+}
+
+/// Resolve the sponsored `PriceUpdateV2` account for a hex `feed_id` and check that it exists.
+pub fn fetch_price_update_account(rpc: &RpcClient, feed_id: &str) -> Result<Pubkey> {
+    let feed_id = get_feed_id_from_hex(feed_id).map_err(|_| ClientError::BadFeedId(feed_id.to_string()))?;
+    let address = price_feed_address(DEFAULT_SHARD_ID, &feed_id);
+
+    let account = rpc
+        .get_account_with_commitment(&address, rpc.commitment())?
+        .value
+        .ok_or(ClientError::NotAPriceUpdate(address))?;
+    if account.owner != pyth_solana_receiver_sdk::ID
+        || PriceUpdateV2::try_deserialize(&mut &account.data[..]).is_err()
+    {
+        return Err(ClientError::NotAPriceUpdate(address));
+    }
+    Ok(address)
+}
+
+/// `read_price` instruction against `price_update`, without the optional cache account. The args
+/// thresholds apply, capped by the program's config once it exists.
+pub fn read_price_ix(program_id: Pubkey, price_update: Pubkey, args: ReadPriceArgs) -> Instruction {
+    let (config, _) = Pubkey::find_program_address(&[b"config"], &program_id);
+    let (allowlist, _) = Pubkey::find_program_address(&[b"allowlist"], &program_id);
+    let mut accounts = crate::accounts::ReadPrice {
+        price_update,
+        config,
+        allowlist,
+        price_cache: None,
+    }
+    .to_account_metas(None);
+    // The generated metas stand in crate::ID for None; a redeployed program expects its own id
+    for meta in &mut accounts {
+        if meta.pubkey == crate::ID {
+            *meta = AccountMeta::new_readonly(program_id, false);
+        }
+    }
+
+    Instruction {
+        program_id,
+        accounts,
+        data: crate::instruction::ReadPrice { args }.data(),
+    }
+}
+
+/// `price * 10^exponent` as an exact decimal string, e.g. `"1823.45"` for 182345 at exponent -2.
+///
+/// Nothing is rounded: a negative exponent always prints that many fractional digits, trailing
+/// zeros included, and a positive one appends zeros. Meant for the small exponents Pyth publishes;
+/// a magnitude past `MAX_FORMAT_EXPONENT` is `ExponentOutOfRange`.
+pub fn format_price(price: i64, exponent: i32) -> Result<String> {
+    if exponent.unsigned_abs() > MAX_FORMAT_EXPONENT {
+        return Err(ClientError::ExponentOutOfRange(exponent));
+    }
+    let sign = if price < 0 { "-" } else { "" };
+    let digits = price.unsigned_abs().to_string();
+    if exponent >= 0 {
+        if price == 0 {
+            return Ok(digits);
+        }
+        return Ok(format!("{sign}{digits}{}", "0".repeat(exponent as usize)));
+    }
+
+    // Left-pad so there is always at least one integer digit, then split off the fraction
+    let scale = exponent.unsigned_abs() as usize;
+    let digits = format!("{digits:0>width$}", width = scale + 1);
+    let (int, frac) = digits.split_at(digits.len() - scale);
+    Ok(format!("{sign}{int}.{frac}"))
+}
+
+/// Send and confirm a `read_price` transaction paid for by `payer`.
+pub fn send_read_price(
+    rpc: &RpcClient,
+    payer: &Keypair,
+    program_id: &Pubkey,
+    price_update: &Pubkey,
+    args: ReadPriceArgs,
+) -> Result<Signature> {
+    let ix = read_price_ix(*program_id, *price_update, args);
+    let blockhash = rpc.get_latest_blockhash()?;
+    let tx = Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[payer], blockhash);
+    Ok(rpc.send_and_confirm_transaction(&tx)?)
+}
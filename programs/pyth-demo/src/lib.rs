@@ -1,55 +1,2309 @@
+#![allow(deprecated)] // anchor 0.31 #[program] codegen still calls AccountInfo::realloc
+
 use anchor_lang::prelude::*;
-use pyth_solana_receiver_sdk::price_update::{ get_feed_id_from_hex, PriceUpdateV2 };
+use pyth_solana_receiver_sdk::error::GetPriceError;
+use pyth_solana_receiver_sdk::price_update::{
+    get_feed_id_from_hex, FeedId, Price, PriceUpdateV2, VerificationLevel,
+};
+
+#[cfg(feature = "client")]
+pub mod client;
+pub mod math;
+pub mod price_update;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+pub mod validation;
+
+use math::{
+    aggregate, cross_ratio, deviation_bps, scale_price, time_weighted_average, usd_value, weighted_average,
+    AggregateMode, RoundingMode,
+};
+use price_update::AnyPriceUpdate;
+use validation::{
+    conf_ratio_bps, observation_age, slots_to_secs, validate_observation, ValidationError, MS_PER_SLOT,
+};
+
+declare_id!("DsdwXUef3WNJkJSohC5238hiTqqEMrHkftKhiyWaURdj"); // replace with your program ID
+
+#[cfg(all(feature = "mainnet", feature = "devnet"))]
+compile_error!("features `mainnet` and `devnet` are mutually exclusive");
+
+const PYTH_RECEIVER_PROGRAM_ID: Pubkey = pyth_solana_receiver_sdk::ID; // required owner of price update accounts
+// Cluster reported by healthcheck; mainnet unless `devnet` is set
+#[cfg(not(feature = "devnet"))]
+const NETWORK: &str = "mainnet";
+#[cfg(feature = "devnet")]
+const NETWORK: &str = "devnet";
+// Pyth feed ids are the same on every cluster, so one value serves both builds
+const DEFAULT_FEED_ID_HEX: &str = "0xff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace"; // ETH/USD, reported by healthcheck
+const PRICE_DECIMALS: u32 = 8; // fixed-point precision of the logged scaled price
+pub const RETURN_DATA_VERSION: u8 = 1; // layout of every price read's return data, bumped on any change
+const MAX_FEEDS: usize = 16; // bounds compute for multi-feed reads
+const FEED_CU_ESTIMATE: u64 = 10_000; // upper bound on CU per feed in multi-feed reads, checked in tests
+const MAX_ALLOWED_FEEDS: usize = 32; // bounds the allowlist account size
+const MAX_AGE_OVERRIDES: usize = 8; // bounds the per-feed max-age overrides in the config account
+const MAX_SYMBOLS: usize = 32; // bounds the symbol registry account size
+const MAX_SYMBOL_LEN: usize = 16; // bytes per registered symbol, e.g. "ETH/USD"
+const MAX_TWAP_CAPACITY: u16 = 64; // bounds the TWAP ring buffer account size
+const DEFAULT_MAX_AGE_SECS: u64 = 60; // reported by healthcheck when no config exists
+const DEFAULT_MAX_CONF_RATIO_BPS: u64 = 200; // reported by healthcheck when no config exists
+
+#[program]
+pub mod pyth_demo {
+    use super::*;
+
+    pub fn read_price(ctx: Context<ReadPrice>, args: ReadPriceArgs) -> Result<VersionedPrice> {
+        let feed_id = args.feed_id()?;
+        check_allowed(&ctx.accounts.allowlist, &feed_id)?;
+        let checks = args.checks_with(load_config(&ctx.accounts.config)?.as_ref(), &feed_id);
+        let price_update = AnyPriceUpdate::try_from_account(&ctx.accounts.price_update)?;
+        let p = load_validated_price(price_update, &feed_id, &checks)?;
+        check_deviation(ctx.accounts.price_cache.as_deref(), &feed_id, &p, args.max_deviation_bps)?;
+        let scaled = scale_price(p.price, p.exponent, PRICE_DECIMALS, RoundingMode::TowardZero)?;
+
+        // Log raw integers plus the price at PRICE_DECIMALS fixed-point decimals
+        if args.verbose {
+            msg!(
+                "price={}, conf={}, exponent={}, t={}, scaled={}",
+                p.price,
+                p.conf,
+                p.exponent,
+                p.publish_time,
+                scaled
+            );
+        }
+
+        // Structured copy of the same observation for indexers
+        emit!(PriceRead {
+            feed_id,
+            price: p.price,
+            conf: p.conf,
+            exponent: p.exponent,
+            publish_time: p.publish_time,
+            slot: Clock::get()?.slot,
+        });
+
+        // Returned via return data so CPI callers can read it with decode_validated_price
+        Ok(VersionedPrice::new(ValidatedPrice::new(feed_id, &p)))
+    }
+
+    /// `read_price`, returning the raw observation together with its value at `target_decimals`.
+    pub fn read_price_full(
+        ctx: Context<ReadPrice>,
+        args: ReadPriceArgs,
+        target_decimals: u32,
+    ) -> Result<VersionedFullPrice> {
+        let feed_id = args.feed_id()?;
+        check_allowed(&ctx.accounts.allowlist, &feed_id)?;
+        let checks = args.checks_with(load_config(&ctx.accounts.config)?.as_ref(), &feed_id);
+        let price_update = AnyPriceUpdate::try_from_account(&ctx.accounts.price_update)?;
+        let p = load_validated_price(price_update, &feed_id, &checks)?;
+        check_deviation(ctx.accounts.price_cache.as_deref(), &feed_id, &p, args.max_deviation_bps)?;
+
+        let full = FullPrice::new(feed_id, &p, target_decimals)?;
+        if args.verbose {
+            msg!(
+                "price={}, conf={}, exponent={}, t={}, value_fixed={}, decimals={}",
+                p.price,
+                p.conf,
+                p.exponent,
+                p.publish_time,
+                full.value_fixed,
+                target_decimals
+            );
+        }
+        Ok(VersionedFullPrice::new(full))
+    }
+
+    pub fn init_scratch(ctx: Context<InitScratch>, authority: Pubkey) -> Result<()> {
+        ctx.accounts.scratch.authority = authority;
+        Ok(())
+    }
+
+    /// `read_price`, with the result written to `scratch` instead of return data.
+    ///
+    /// A failed read aborts the whole transaction, CPI included, so the account reverts to its
+    /// previous contents and never holds an unvalidated price. Readers should still check the
+    /// feed id and publish time they find.
+    pub fn read_price_into(ctx: Context<ReadPriceInto>, args: ReadPriceArgs) -> Result<()> {
+        let feed_id = args.feed_id()?;
+        args.require_no_deviation_limit()?;
+        check_allowed(&ctx.accounts.allowlist, &feed_id)?;
+        let checks = args.checks_with(load_config(&ctx.accounts.config)?.as_ref(), &feed_id);
+        let price_update = AnyPriceUpdate::try_from_account(&ctx.accounts.price_update)?;
+        let p = load_validated_price(price_update, &feed_id, &checks)?;
+
+        ctx.accounts.scratch.price = ValidatedPrice::new(feed_id, &p);
+        msg!("wrote price={}, t={} to {}", p.price, p.publish_time, ctx.accounts.scratch.key());
+        Ok(())
+    }
+
+    pub fn read_ema_price(ctx: Context<ReadPrice>, args: ReadPriceArgs) -> Result<()> {
+        let feed_id = args.feed_id()?;
+        args.require_no_deviation_limit()?;
+        check_allowed(&ctx.accounts.allowlist, &feed_id)?;
+        let checks = args.checks_with(load_config(&ctx.accounts.config)?.as_ref(), &feed_id);
+        let price_update = AnyPriceUpdate::try_from_account(&ctx.accounts.price_update)?;
+        let p = load_validated_ema_price(price_update, &feed_id, &checks)?;
+
+        msg!(
+            "ema_price={}, ema_conf={}, exponent={}, t={}",
+            p.price,
+            p.conf,
+            p.exponent,
+            p.publish_time
+        );
+
+        Ok(())
+    }
+
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        max_age_secs: u64,
+        max_conf_ratio_bps: u64,
+    ) -> Result<()> {
+        require!(max_age_secs > 0, ErrorCode::InvalidArgs);
+
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.admin.key();
+        config.max_age_secs = max_age_secs;
+        config.max_conf_ratio_bps = max_conf_ratio_bps;
+        config.bump = ctx.bumps.config;
+
+        Ok(())
+    }
+
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        max_age_secs: u64,
+        max_conf_ratio_bps: u64,
+    ) -> Result<()> {
+        require!(max_age_secs > 0, ErrorCode::InvalidArgs);
+
+        let config = &mut ctx.accounts.config;
+        config.max_age_secs = max_age_secs;
+        config.max_conf_ratio_bps = max_conf_ratio_bps;
+
+        msg!(
+            "config updated: max_age_secs={}, max_conf_ratio_bps={}",
+            max_age_secs,
+            max_conf_ratio_bps
+        );
+
+        Ok(())
+    }
+
+    /// Give `feed_id` its own max age, used in place of `config.max_age_secs` for that feed.
+    pub fn set_max_age_override(
+        ctx: Context<UpdateConfig>,
+        feed_id: [u8; 32],
+        max_age_secs: u64,
+    ) -> Result<()> {
+        require!(max_age_secs > 0, ErrorCode::InvalidArgs);
+        check_feed_id(&feed_id)?;
+
+        let overrides = &mut ctx.accounts.config.max_age_overrides;
+        match overrides.iter_mut().find(|o| o.feed_id == feed_id) {
+            Some(existing) => existing.max_age_secs = max_age_secs,
+            None => {
+                require!(overrides.len() < MAX_AGE_OVERRIDES, ErrorCode::TooManyOverrides);
+                overrides.push(MaxAgeOverride {
+                    feed_id,
+                    max_age_secs,
+                });
+            }
+        }
+
+        msg!("max age override set: max_age_secs={}", max_age_secs);
+        Ok(())
+    }
+
+    /// Drop `feed_id`'s override so it falls back to `config.max_age_secs`.
+    pub fn clear_max_age_override(ctx: Context<UpdateConfig>, feed_id: [u8; 32]) -> Result<()> {
+        let overrides = &mut ctx.accounts.config.max_age_overrides;
+        let i = overrides
+            .iter()
+            .position(|o| o.feed_id == feed_id)
+            .ok_or(error!(ErrorCode::OverrideNotFound))?;
+        overrides.swap_remove(i);
+
+        msg!("max age override cleared");
+        Ok(())
+    }
+
+    /// Restrict `cache_price` and `cache_prices` to `cache_writer`, or open them to anyone with None.
+    pub fn set_cache_writer(ctx: Context<UpdateConfig>, cache_writer: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.config.cache_writer = cache_writer;
+
+        match cache_writer {
+            Some(writer) => msg!("cache writer set: {}", writer),
+            None => msg!("cache writer cleared, cache writes are permissionless"),
+        }
+        Ok(())
+    }
+
+    /// Validate every feed, tolerating up to `max_stale_allowed` that are merely too old; past that,
+    /// or on any other failure, the read reverts. Returns a mask with bit `i` set when feed `i` was
+    /// stale.
+    pub fn read_prices<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ReadPrices<'info>>,
+        args: ReadPricesArgs,
+        max_stale_allowed: u8,
+    ) -> Result<u16> {
+        // MAX_FEEDS also keeps every feed's bit inside the u16 mask
+        let n = args.feed_ids.len();
+        require!(n > 0 && n <= MAX_FEEDS, ErrorCode::TooManyFeeds);
+        require!(ctx.remaining_accounts.len() == n, ErrorCode::InvalidArgs);
+        check_compute_budget(n)?;
+        check_all_allowed(&ctx.accounts.allowlist, &args.feed_ids)?;
+
+        let checks = args.checks();
+        let config = load_config(&ctx.accounts.config)?;
+        let mut stale = 0u16;
+        for (i, (info, feed_id)) in ctx.remaining_accounts.iter().zip(&args.feed_ids).enumerate() {
+            let checks = checks.with_config(config.as_ref(), feed_id);
+            let Some(p) = load_unless_stale(info, feed_id, &checks, i)? else {
+                stale |= 1 << i;
+                continue;
+            };
+
+            msg!(
+                "feed={}, price={}, conf={}, exponent={}, t={}",
+                i,
+                p.price,
+                p.conf,
+                p.exponent,
+                p.publish_time
+            );
+        }
+
+        if stale.count_ones() > u32::from(max_stale_allowed) {
+            msg!("{} of {} feeds stale, {} allowed", stale.count_ones(), n, max_stale_allowed);
+            return err!(ErrorCode::TooManyStaleFeeds);
+        }
+        Ok(stale)
+    }
+
+    pub fn basket_price<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ReadPrices<'info>>,
+        args: BasketPriceArgs,
+    ) -> Result<BasketPrice> {
+        let n = args.feed_ids.len();
+        require!(n > 0 && n <= MAX_FEEDS, ErrorCode::TooManyFeeds);
+        require!(
+            args.weights.len() == n && ctx.remaining_accounts.len() == n,
+            ErrorCode::InvalidArgs
+        );
+        check_compute_budget(n)?;
+        check_all_allowed(&ctx.accounts.allowlist, &args.feed_ids)?;
+
+        let checks = args.checks();
+        let config = load_config(&ctx.accounts.config)?;
+        let mut items = Vec::with_capacity(n);
+        for (i, (info, feed_id)) in ctx.remaining_accounts.iter().zip(&args.feed_ids).enumerate() {
+            let checks = checks.with_config(config.as_ref(), feed_id);
+            let p = AnyPriceUpdate::try_from_account(info)
+                .and_then(|price_update| load_validated_price(price_update, feed_id, &checks))
+                .inspect_err(|_| msg!("feed {} failed validation", i))?;
+            // Feeds have their own exponents, so bring them to common decimals before weighting
+            let scaled = scale_price(p.price, p.exponent, args.target_decimals, RoundingMode::TowardZero)?;
+            items.push((scaled, args.weights[i]));
+        }
+
+        let price = weighted_average(&items)?;
+        msg!("basket price={}, decimals={}, feeds={}", price, args.target_decimals, n);
+
+        Ok(BasketPrice {
+            price,
+            decimals: args.target_decimals,
+        })
+    }
+
+    pub fn aggregate_prices<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ReadPrices<'info>>,
+        args: AggregatePricesArgs,
+    ) -> Result<AggregatePrice> {
+        let n = args.feed_ids.len();
+        require!(n > 0, ErrorCode::NoFeeds);
+        require!(n <= MAX_FEEDS, ErrorCode::TooManyFeeds);
+        require!(ctx.remaining_accounts.len() == n, ErrorCode::InvalidArgs);
+        check_compute_budget(n)?;
+        check_all_allowed(&ctx.accounts.allowlist, &args.feed_ids)?;
+
+        let checks = args.checks();
+        let config = load_config(&ctx.accounts.config)?;
+        let mut values = Vec::with_capacity(n);
+        for (i, (info, feed_id)) in ctx.remaining_accounts.iter().zip(&args.feed_ids).enumerate() {
+            let checks = checks.with_config(config.as_ref(), feed_id);
+            let p = AnyPriceUpdate::try_from_account(info)
+                .and_then(|price_update| load_validated_price(price_update, feed_id, &checks))
+                .inspect_err(|_| msg!("feed {} failed validation", i))?;
+            values.push(scale_price(p.price, p.exponent, args.target_decimals, RoundingMode::TowardZero)?);
+        }
+
+        let price = aggregate(&mut values, args.mode)?;
+        msg!("{:?} price={}, decimals={}, feeds={}", args.mode, price, args.target_decimals, n);
+
+        Ok(AggregatePrice {
+            price,
+            decimals: args.target_decimals,
+        })
+    }
+
+    pub fn init_cache(ctx: Context<InitCache>, feed_id: [u8; 32]) -> Result<()> {
+        check_feed_id(&feed_id)?;
+
+        // Nothing is cached until the first cache_price; publish_time 0 marks that
+        let cache = &mut ctx.accounts.price_cache;
+        cache.feed_id = feed_id;
+        cache.bump = ctx.bumps.price_cache;
+
+        Ok(())
+    }
+
+    pub fn cache_price(ctx: Context<CachePrice>, args: ReadPriceArgs) -> Result<()> {
+        let feed_id = args.feed_id()?;
+        check_allowed(&ctx.accounts.allowlist, &feed_id)?;
+        let config = load_config(&ctx.accounts.config)?;
+        check_cache_writer(config.as_ref(), ctx.accounts.payer.key)?;
+        let checks = args.checks_with(config.as_ref(), &feed_id);
+        let price_update = AnyPriceUpdate::try_from_account(&ctx.accounts.price_update)?;
+        let p = load_validated_price(price_update, &feed_id, &checks)?;
+        check_cache_deviation(&ctx.accounts.price_cache, &p, args.max_deviation_bps)?;
+        ctx.accounts.price_cache.record(feed_id, &p, ctx.bumps.price_cache)
+    }
+
+    /// `cache_price` for many feeds at once, for keepers. `remaining_accounts` holds a
+    /// `(price_update, price_cache)` pair per feed, in `feed_ids` order, and every cache must already
+    /// exist (see `init_cache`). A feed that is merely too old, or no newer than its cache, is skipped;
+    /// any other failure reverts. Returns a mask with bit `i` set when feed `i` was written.
+    pub fn cache_prices<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CachePrices<'info>>,
+        args: ReadPricesArgs,
+    ) -> Result<u16> {
+        // MAX_FEEDS also keeps every feed's bit inside the u16 mask
+        let n = args.feed_ids.len();
+        require!(n > 0 && n <= MAX_FEEDS, ErrorCode::TooManyFeeds);
+        require!(ctx.remaining_accounts.len() == 2 * n, ErrorCode::InvalidArgs);
+        check_compute_budget(n)?;
+        check_all_allowed(&ctx.accounts.allowlist, &args.feed_ids)?;
+        let config = load_config(&ctx.accounts.config)?;
+        check_cache_writer(config.as_ref(), ctx.accounts.writer.key)?;
+
+        let checks = args.checks();
+        let mut updated = 0u16;
+        for (i, (pair, feed_id)) in ctx.remaining_accounts.chunks_exact(2).zip(&args.feed_ids).enumerate() {
+            let checks = checks.with_config(config.as_ref(), feed_id);
+            let Some(p) = load_unless_stale(&pair[0], feed_id, &checks, i)? else {
+                continue;
+            };
+            if write_cache(&pair[1], feed_id, &p, i)? {
+                updated |= 1 << i;
+            }
+        }
+
+        msg!("cached {} of {} feeds", updated.count_ones(), n);
+        Ok(updated)
+    }
+
+    pub fn read_price_signed_by_authority(
+        ctx: Context<CachePriceSigned>,
+        args: ReadPriceArgs,
+    ) -> Result<VersionedPrice> {
+        // Only the config admin gets here (has_one); the config thresholds apply as for any read
+        let feed_id = args.feed_id()?;
+        check_allowed(&ctx.accounts.allowlist, &feed_id)?;
+        let checks = args.checks_with(Some(&ctx.accounts.config), &feed_id);
+        let price_update = AnyPriceUpdate::try_from_account(&ctx.accounts.price_update)?;
+        let p = load_validated_price(price_update, &feed_id, &checks)?;
+        check_cache_deviation(&ctx.accounts.price_cache, &p, args.max_deviation_bps)?;
+        ctx.accounts.price_cache.record(feed_id, &p, ctx.bumps.price_cache)?;
+        Ok(VersionedPrice::new(ValidatedPrice::new(feed_id, &p)))
+    }
+
+    pub fn close_cache(ctx: Context<CloseCache>) -> Result<()> {
+        // Anchor's close moves the lamports and zeroes the account once this returns
+        msg!(
+            "closing cache t={}, refund to {}",
+            ctx.accounts.price_cache.publish_time,
+            ctx.accounts.receiver.key()
+        );
+        Ok(())
+    }
+
+    pub fn add_feed(ctx: Context<AddFeed>, feed_id: [u8; 32]) -> Result<()> {
+        check_feed_id(&feed_id)?;
+        let allowlist = &mut ctx.accounts.allowlist;
+        allowlist.bump = ctx.bumps.allowlist;
+        if !allowlist.feed_ids.contains(&feed_id) {
+            require!(allowlist.feed_ids.len() < MAX_ALLOWED_FEEDS, ErrorCode::AllowlistFull);
+            allowlist.feed_ids.push(feed_id);
+        }
+
+        Ok(())
+    }
+
+    pub fn remove_feed(ctx: Context<RemoveFeed>, feed_id: [u8; 32]) -> Result<()> {
+        let feed_ids = &mut ctx.accounts.allowlist.feed_ids;
+        let index = feed_ids
+            .iter()
+            .position(|id| *id == feed_id)
+            .ok_or(error!(ErrorCode::FeedNotAllowed))?;
+        feed_ids.swap_remove(index);
+
+        Ok(())
+    }
+
+    /// Map `symbol` to `feed_id`, replacing any feed it was mapped to before.
+    pub fn register_symbol(ctx: Context<RegisterSymbol>, symbol: String, feed_id: [u8; 32]) -> Result<()> {
+        require!(!symbol.is_empty(), ErrorCode::InvalidArgs);
+        require!(symbol.len() <= MAX_SYMBOL_LEN, ErrorCode::SymbolTooLong);
+        check_feed_id(&feed_id)?;
+
+        let registry = &mut ctx.accounts.registry;
+        registry.bump = ctx.bumps.registry;
+        match registry.entries.iter_mut().find(|e| e.symbol == symbol) {
+            Some(entry) => entry.feed_id = feed_id,
+            None => {
+                require!(registry.entries.len() < MAX_SYMBOLS, ErrorCode::RegistryFull);
+                registry.entries.push(SymbolEntry { symbol, feed_id });
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn unregister_symbol(ctx: Context<UnregisterSymbol>, symbol: String) -> Result<()> {
+        let entries = &mut ctx.accounts.registry.entries;
+        let index = entries
+            .iter()
+            .position(|e| e.symbol == symbol)
+            .ok_or(error!(ErrorCode::UnknownSymbol))?;
+        entries.swap_remove(index);
+
+        Ok(())
+    }
+
+    /// `read_price` for the feed registered under `symbol`. The feed id fields of `args` must be
+    /// left empty, so the registry is the only source of the feed.
+    pub fn read_by_symbol(
+        ctx: Context<ReadBySymbol>,
+        symbol: String,
+        args: ReadPriceArgs,
+    ) -> Result<VersionedPrice> {
+        require!(
+            args.feed_id == [0u8; 32] && args.feed_id_hex.is_empty(),
+            ErrorCode::InvalidArgs
+        );
+        args.require_no_deviation_limit()?;
+        let feed_id = ctx.accounts.registry.feed_id(&symbol)?;
+        check_allowed(&ctx.accounts.allowlist, &feed_id)?;
+        let checks = args.checks_with(load_config(&ctx.accounts.config)?.as_ref(), &feed_id);
+        let price_update = AnyPriceUpdate::try_from_account(&ctx.accounts.price_update)?;
+        let p = load_validated_price(price_update, &feed_id, &checks)?;
+
+        if args.verbose {
+            msg!("symbol={}, price={}, exponent={}, t={}", symbol, p.price, p.exponent, p.publish_time);
+        }
+        Ok(VersionedPrice::new(ValidatedPrice::new(feed_id, &p)))
+    }
+
+    pub fn value_in_usd(
+        ctx: Context<ReadPrice>,
+        args: ReadPriceArgs,
+        amount: u64,
+        token_decimals: u8,
+        target_decimals: u32,
+        rounding: RoundingMode,
+    ) -> Result<()> {
+        let feed_id = args.feed_id()?;
+        args.require_no_deviation_limit()?;
+        check_allowed(&ctx.accounts.allowlist, &feed_id)?;
+        let checks = args.checks_with(load_config(&ctx.accounts.config)?.as_ref(), &feed_id);
+        let price_update = AnyPriceUpdate::try_from_account(&ctx.accounts.price_update)?;
+        let p = load_validated_price(price_update, &feed_id, &checks)?;
+        require!(p.price > 0, ErrorCode::NegativePrice);
+
+        let value = usd_value(amount, token_decimals, p.price, p.exponent, target_decimals, rounding)?;
+
+        msg!("amount={}, decimals={}, usd_value={}", amount, token_decimals, value);
+        emit!(UsdValue {
+            feed_id,
+            amount,
+            token_decimals,
+            price: p.price,
+            exponent: p.exponent,
+            value,
+            value_decimals: target_decimals,
+        });
+
+        Ok(())
+    }
+
+    pub fn read_price_no_older_than_slots(
+        ctx: Context<ReadPrice>,
+        args: ReadPriceArgs,
+        max_age_slots: u64,
+    ) -> Result<VersionedPrice> {
+        let feed_id = args.feed_id()?;
+        args.require_no_deviation_limit()?;
+        check_allowed(&ctx.accounts.allowlist, &feed_id)?;
+        let checks = args.checks_with(load_config(&ctx.accounts.config)?.as_ref(), &feed_id);
+        let price_update = AnyPriceUpdate::try_from_account(&ctx.accounts.price_update)?;
+        let p = get_price_no_older_than_slots(price_update, &feed_id, max_age_slots, &checks)?;
+
+        msg!(
+            "price={}, conf={}, exponent={}, t={}",
+            p.price,
+            p.conf,
+            p.exponent,
+            p.publish_time
+        );
+        Ok(VersionedPrice::new(ValidatedPrice::new(feed_id, &p)))
+    }
+
+    pub fn read_price_in_range(
+        ctx: Context<ReadPrice>,
+        args: ReadPriceArgs,
+        min_price: i64,
+        max_price: i64,
+    ) -> Result<VersionedPrice> {
+        require!(min_price <= max_price, ErrorCode::InvalidArgs);
+        let feed_id = args.feed_id()?;
+        args.require_no_deviation_limit()?;
+        check_allowed(&ctx.accounts.allowlist, &feed_id)?;
+        let checks = args.checks_with(load_config(&ctx.accounts.config)?.as_ref(), &feed_id);
+        let price_update = AnyPriceUpdate::try_from_account(&ctx.accounts.price_update)?;
+        let p = load_validated_price(price_update, &feed_id, &checks)?;
+
+        // Inclusive bounds in the feed's native exponent; signed, so negative prices order correctly
+        if !(min_price..=max_price).contains(&p.price) {
+            msg!("price={} outside [{}, {}]", p.price, min_price, max_price);
+            return err!(ErrorCode::PriceOutOfRange);
+        }
+
+        msg!(
+            "in range: price={}, conf={}, exponent={}, t={}",
+            p.price,
+            p.conf,
+            p.exponent,
+            p.publish_time
+        );
+        Ok(VersionedPrice::new(ValidatedPrice::new(feed_id, &p)))
+    }
+
+    pub fn read_price_with_slippage(
+        ctx: Context<ReadPrice>,
+        args: ReadPriceArgs,
+        expected_price: i64,
+        max_slippage_bps: u64,
+    ) -> Result<VersionedPrice> {
+        require!(expected_price != 0, ErrorCode::InvalidArgs);
+        let feed_id = args.feed_id()?;
+        args.require_no_deviation_limit()?;
+        check_allowed(&ctx.accounts.allowlist, &feed_id)?;
+        let checks = args.checks_with(load_config(&ctx.accounts.config)?.as_ref(), &feed_id);
+        let price_update = AnyPriceUpdate::try_from_account(&ctx.accounts.price_update)?;
+        let p = load_validated_price(price_update, &feed_id, &checks)?;
+
+        // expected_price is in the feed's native exponent; the band is measured against it in u128
+        let slippage = deviation_bps(p.price, expected_price)?;
+        if slippage > u128::from(max_slippage_bps) {
+            msg!(
+                "price={} is {} bps from expected={}, max {} bps",
+                p.price,
+                slippage,
+                expected_price,
+                max_slippage_bps
+            );
+            return err!(ErrorCode::SlippageExceeded);
+        }
+
+        msg!("price={}, slippage={} bps, t={}", p.price, slippage, p.publish_time);
+        Ok(VersionedPrice::new(ValidatedPrice::new(feed_id, &p)))
+    }
+
+    pub fn read_price_near_ema(
+        ctx: Context<ReadPrice>,
+        args: ReadPriceArgs,
+        max_divergence_bps: u64,
+    ) -> Result<VersionedPrice> {
+        require!(max_divergence_bps > 0, ErrorCode::InvalidArgs);
+        let feed_id = args.feed_id()?;
+        args.require_no_deviation_limit()?;
+        check_allowed(&ctx.accounts.allowlist, &feed_id)?;
+        let checks = args.checks_with(load_config(&ctx.accounts.config)?.as_ref(), &feed_id);
+        let price_update = AnyPriceUpdate::try_from_account(&ctx.accounts.price_update)?;
+        let p = load_validated_price(price_update.clone(), &feed_id, &checks)?;
+        // Same message as the spot price, so it is already verified and just as fresh
+        let ema = price_update.get_ema_price_unchecked(&feed_id)?;
+        let divergence = check_divergence(&p, &ema, max_divergence_bps)?;
+
+        msg!(
+            "price={}, ema_price={}, divergence={} bps, t={}",
+            p.price,
+            ema.price,
+            divergence,
+            p.publish_time
+        );
+        Ok(VersionedPrice::new(ValidatedPrice::new(feed_id, &p)))
+    }
+
+    pub fn cross_price(ctx: Context<CrossPrice>, args: CrossPriceArgs) -> Result<VersionedCrossRate> {
+        check_all_allowed(&ctx.accounts.allowlist, &[args.base_feed_id, args.quote_feed_id])?;
+        let config = load_config(&ctx.accounts.config)?;
+        let checks = PriceChecks {
+            reject_negative: args.reject_negative,
+            ..PriceChecks::new(args.max_age_secs, args.max_conf_ratio_bps)
+        };
+        // Each leg gets its own feed's override
+        let base = load_validated_price(
+            AnyPriceUpdate::try_from_account(&ctx.accounts.base_price_update)?,
+            &args.base_feed_id,
+            &checks.with_config(config.as_ref(), &args.base_feed_id),
+        )?;
+        let quote = load_validated_price(
+            AnyPriceUpdate::try_from_account(&ctx.accounts.quote_price_update)?,
+            &args.quote_feed_id,
+            &checks.with_config(config.as_ref(), &args.quote_feed_id),
+        )?;
+
+        let ratio = cross_ratio(
+            base.price,
+            base.exponent,
+            quote.price,
+            quote.exponent,
+            args.target_decimals,
+        )?;
+
+        msg!("cross ratio={}, decimals={}", ratio, args.target_decimals);
+        emit!(CrossRate {
+            base_feed_id: args.base_feed_id,
+            quote_feed_id: args.quote_feed_id,
+            ratio,
+            decimals: args.target_decimals,
+            base_publish_time: base.publish_time,
+            quote_publish_time: quote.publish_time,
+        });
+
+        // Same shape as the event, versioned like every other read so CPI callers can decode it
+        Ok(VersionedCrossRate::new(ValidatedCrossRate {
+            base_feed_id: args.base_feed_id,
+            quote_feed_id: args.quote_feed_id,
+            ratio,
+            decimals: args.target_decimals,
+            base_publish_time: base.publish_time,
+            quote_publish_time: quote.publish_time,
+        }))
+    }
+
+    pub fn read_price_or_cached(
+        ctx: Context<ReadPriceOrCached>,
+        args: ReadPriceArgs,
+        allow_stale: bool,
+    ) -> Result<VersionedMaybeStalePrice> {
+        let feed_id = args.feed_id()?;
+        check_allowed(&ctx.accounts.allowlist, &feed_id)?;
+        let checks = args.checks_with(load_config(&ctx.accounts.config)?.as_ref(), &feed_id);
+
+        // The fallback is the cache itself, so only a fresh price is compared with it
+        let price_update = AnyPriceUpdate::try_from_account(&ctx.accounts.price_update)?;
+        match load_validated_price(price_update, &feed_id, &checks) {
+            Ok(p) => {
+                check_cache_deviation(&ctx.accounts.price_cache, &p, args.max_deviation_bps)?;
+                Ok(VersionedMaybeStalePrice::new(MaybeStalePrice {
+                    price: ValidatedPrice::new(feed_id, &p),
+                    stale: false,
+                }))
+            }
+            // Only staleness falls back, and only to a written cache; every other failure reverts
+            Err(e)
+                if allow_stale
+                    && e == ErrorCode::PriceTooOld.into()
+                    && ctx.accounts.price_cache.is_recorded() =>
+            {
+                let cache = &ctx.accounts.price_cache;
+                msg!("price too old, using cached price from t={}", cache.publish_time);
+                Ok(VersionedMaybeStalePrice::new(MaybeStalePrice {
+                    price: ValidatedPrice {
+                        feed_id: cache.feed_id,
+                        price: cache.price,
+                        conf: cache.conf,
+                        exponent: cache.exponent,
+                        publish_time: cache.publish_time,
+                    },
+                    stale: true,
+                }))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn read_price_batch_same_feed<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ReadPrices<'info>>,
+        args: ReadPriceArgs,
+    ) -> Result<VersionedPrice> {
+        let n = ctx.remaining_accounts.len();
+        require!(n > 0 && n <= MAX_FEEDS, ErrorCode::TooManyFeeds);
+        check_compute_budget(n)?;
+
+        let feed_id = args.feed_id()?;
+        args.require_no_deviation_limit()?;
+        check_allowed(&ctx.accounts.allowlist, &feed_id)?;
+        let checks = args.checks_with(load_config(&ctx.accounts.config)?.as_ref(), &feed_id);
+        let mut newest: Option<Price> = None;
+        for (i, info) in ctx.remaining_accounts.iter().enumerate() {
+            let p = AnyPriceUpdate::try_from_account(info)
+                .and_then(|price_update| load_validated_price(price_update, &feed_id, &checks))
+                .inspect_err(|_| msg!("update {} failed validation", i))?;
+
+            // Updates must arrive oldest first with no repeated observation
+            if let Some(prev) = &newest {
+                if p.publish_time <= prev.publish_time {
+                    msg!("update {} t={} is not after t={}", i, p.publish_time, prev.publish_time);
+                    return err!(ErrorCode::NonMonotonicPrices);
+                }
+            }
+            newest = Some(p);
+        }
+
+        // n > 0, so at least one update was validated
+        let p = newest.ok_or(error!(ErrorCode::TooManyFeeds))?;
+        msg!(
+            "newest of {}: price={}, conf={}, exponent={}, t={}",
+            n,
+            p.price,
+            p.conf,
+            p.exponent,
+            p.publish_time
+        );
+
+        Ok(VersionedPrice::new(ValidatedPrice::new(feed_id, &p)))
+    }
+
+    pub fn init_twap(ctx: Context<InitTwap>, feed_id: [u8; 32], capacity: u16) -> Result<()> {
+        require!(
+            (2..=MAX_TWAP_CAPACITY).contains(&capacity),
+            ErrorCode::InvalidArgs
+        );
+        check_feed_id(&feed_id)?;
+
+        let twap = &mut ctx.accounts.twap_state;
+        twap.feed_id = feed_id;
+        twap.capacity = capacity;
+        twap.bump = ctx.bumps.twap_state;
+
+        Ok(())
+    }
+
+    pub fn record_sample(ctx: Context<RecordSample>, args: ReadPriceArgs) -> Result<()> {
+        let feed_id = args.feed_id()?;
+        args.require_no_deviation_limit()?;
+        check_allowed(&ctx.accounts.allowlist, &feed_id)?;
+        let checks = args.checks_with(load_config(&ctx.accounts.config)?.as_ref(), &feed_id);
+        let price_update = AnyPriceUpdate::try_from_account(&ctx.accounts.price_update)?;
+        let p = load_validated_price(price_update, &feed_id, &checks)?;
+
+        let twap = &mut ctx.accounts.twap_state;
+        if twap.latest().is_some_and(|s| p.publish_time <= s.publish_time) {
+            // Same observation posted again (or an older one): nothing new to accumulate
+            msg!("sample ignored: t={} already recorded", p.publish_time);
+            return Ok(());
+        }
+        if twap.samples.is_empty() {
+            twap.exponent = p.exponent;
+        }
+        require!(p.exponent == twap.exponent, ErrorCode::ExponentChanged);
+
+        twap.push(TwapSample {
+            price: p.price,
+            publish_time: p.publish_time,
+        });
+
+        msg!("sample recorded: price={}, t={}", p.price, p.publish_time);
+
+        Ok(())
+    }
+
+    pub fn read_twap(ctx: Context<ReadTwap>) -> Result<TwapResult> {
+        let twap = &ctx.accounts.twap_state;
+        let samples = twap.chronological();
+        let price = time_weighted_average(&samples)?;
+
+        let result = TwapResult {
+            feed_id: twap.feed_id,
+            price,
+            exponent: twap.exponent,
+            start_time: samples[0].1,
+            end_time: samples[samples.len() - 1].1,
+            num_samples: samples.len() as u16,
+        };
+
+        msg!(
+            "twap={}, exponent={}, window={}..{}",
+            result.price,
+            result.exponent,
+            result.start_time,
+            result.end_time
+        );
+
+        Ok(result)
+    }
+
+    pub fn price_age(ctx: Context<ReadPrice>, feed_id: [u8; 32]) -> Result<i64> {
+        check_allowed(&ctx.accounts.allowlist, &feed_id)?;
+        // Structure, feed and verification only; staleness is the answer here, not an error
+        let price_update = AnyPriceUpdate::try_from_account(&ctx.accounts.price_update)?;
+        let p = load_price(&price_update, &feed_id)?;
+        // 0 within the tolerated clock skew; further ahead is FuturePrice rather than a negative age
+        let age = observation_age(p.publish_time, now()?)?;
+        let age = i64::try_from(age).map_err(|_| error!(ErrorCode::MathOverflow))?;
+
+        msg!("age={}s, t={}", age, p.publish_time);
+        Ok(age)
+    }
+
+    pub fn healthcheck(ctx: Context<Healthcheck>) -> Result<Health> {
+        let config = load_config(&ctx.accounts.config)?;
+        let config = config.as_ref();
+        let default_feed_id = get_feed_id_from_hex(DEFAULT_FEED_ID_HEX).map_err(|_| error!(ErrorCode::BadFeedId))?;
+        let health = Health {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            network: NETWORK.to_string(),
+            default_feed_id,
+            configured: config.is_some(),
+            max_age_secs: config.map_or(DEFAULT_MAX_AGE_SECS, |c| c.max_age_secs),
+            max_conf_ratio_bps: config.map_or(DEFAULT_MAX_CONF_RATIO_BPS, |c| c.max_conf_ratio_bps),
+            slot: Clock::get()?.slot,
+        };
+
+        msg!(
+            "version={}, network={}, configured={}, max_age_secs={}, max_conf_ratio_bps={}, slot={}",
+            health.version,
+            health.network,
+            health.configured,
+            health.max_age_secs,
+            health.max_conf_ratio_bps,
+            health.slot
+        );
+        Ok(health)
+    }
+
+    pub fn simulate_read(ctx: Context<SimulateRead>, args: ReadPriceArgs) -> Result<SimulatedRead> {
+        let read = classify_read(
+            &ctx.accounts.price_update,
+            &ctx.accounts.allowlist,
+            &args,
+            load_config(&ctx.accounts.config)?.as_ref(),
+            now()?,
+        );
+
+        match &read.price {
+            Some(p) => msg!(
+                "status={:?}, price={}, conf={}, exponent={}, t={}",
+                read.status,
+                p.price,
+                p.conf,
+                p.exponent,
+                p.publish_time
+            ),
+            None => msg!("status={:?}", read.status),
+        }
+
+        // Reported through return data, never as an error
+        Ok(read)
+    }
+}
+
+/// Fail up front if the compute left in the transaction can't cover `feeds` reads, instead of
+/// running out partway through. The remaining-units syscall already accounts for the requested
+/// limit and for instructions that ran earlier, so the instructions sysvar isn't needed.
+fn check_compute_budget(feeds: usize) -> Result<()> {
+    let needed = FEED_CU_ESTIMATE * feeds as u64;
+    let remaining = anchor_lang::solana_program::compute_units::sol_remaining_compute_units();
+    if remaining < needed {
+        msg!(
+            "{} feeds need ~{} CU ({} each), {} left; raise the limit with SetComputeUnitLimit",
+            feeds,
+            needed,
+            FEED_CU_ESTIMATE,
+            remaining
+        );
+        return err!(ErrorCode::InsufficientComputeBudget);
+    }
+    Ok(())
+}
+
+/// Reject `feed_id` if the allowlist PDA exists and does not contain it.
+fn check_allowed(allowlist: &AccountInfo, feed_id: &FeedId) -> Result<()> {
+    check_all_allowed(allowlist, core::slice::from_ref(feed_id))
+}
+
+/// `check_allowed` for every feed of a multi-feed instruction, decoding the allowlist once.
+fn check_all_allowed(allowlist: &AccountInfo, feed_ids: &[FeedId]) -> Result<()> {
+    // No allowlist account means the deployment is permissionless
+    if allowlist.data_is_empty() {
+        return Ok(());
+    }
+    require_keys_eq!(*allowlist.owner, crate::ID, ErrorCode::FeedNotAllowed);
+    let allowlist = Allowlist::try_deserialize(&mut &allowlist.try_borrow_data()?[..])?;
+    if let Some(feed_id) = feed_ids.iter().find(|id| !allowlist.feed_ids.contains(id)) {
+        msg!("feed 0x{} is not on the allowlist", hex(feed_id));
+        return err!(ErrorCode::FeedNotAllowed);
+    }
+    Ok(())
+}
+
+/// Reject a cache write from anyone but the config's `cache_writer`, once one is set.
+fn check_cache_writer(config: Option<&OracleConfig>, writer: &Pubkey) -> Result<()> {
+    if let Some(cache_writer) = config.and_then(|c| c.cache_writer) {
+        require_keys_eq!(*writer, cache_writer, ErrorCode::UnauthorizedCacheWriter);
+    }
+    Ok(())
+}
+
+/// The config PDA's thresholds, or None until `initialize_config` has created it.
+///
+/// Taken as an address-pinned account rather than an optional one, so a caller can't shed the
+/// admin's limits by leaving the config out.
+fn load_config(config: &AccountInfo) -> Result<Option<OracleConfig>> {
+    if config.data_is_empty() {
+        return Ok(None);
+    }
+    require_keys_eq!(
+        *config.owner,
+        crate::ID,
+        anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram
+    );
+    OracleConfig::try_deserialize(&mut &config.try_borrow_data()?[..]).map(Some)
+}
+
+/// Decode raw `PriceUpdateV2` account data, e.g. bytes from `getAccountInfo` or an `AccountInfo`
+/// that may hold something else. Only the layout is checked; the caller must verify the owner.
+pub fn decode_price_update(data: &[u8]) -> Result<PriceUpdateV2> {
+    require!(
+        data.starts_with(PriceUpdateV2::DISCRIMINATOR),
+        ErrorCode::NotAPriceUpdate
+    );
+    PriceUpdateV2::try_deserialize_unchecked(&mut &data[..]).map_err(|_| error!(ErrorCode::NotAPriceUpdate))
+}
+
+/// The checks of `read_price`, reported as a `ReadStatus` instead of an error. The deviation check is
+/// not among them: it needs the price cache, which `simulate_read` does not take, so a nonzero
+/// `max_deviation_bps` is `InvalidArgs`.
+fn classify_read(
+    price_update: &AccountInfo,
+    allowlist: &AccountInfo,
+    args: &ReadPriceArgs,
+    config: Option<&OracleConfig>,
+    now: i64,
+) -> SimulatedRead {
+    let loaded = args.feed_id().and_then(|feed_id| {
+        check_allowed(allowlist, &feed_id)?;
+        // Decoded here rather than by Account so a bad account is a status, not a revert
+        let price_update = AnyPriceUpdate::try_from_account(price_update)?;
+        Ok((feed_id, load_price(&price_update, &feed_id)?))
+    });
+    let Ok((feed_id, p)) = loaded else {
+        return SimulatedRead {
+            status: ReadStatus::BadFeed,
+            price: None,
+        };
+    };
+    let checks = args.checks_with(config, &feed_id);
+
+    let status = match validate_observation(
+        p.price,
+        p.conf,
+        p.exponent,
+        p.publish_time,
+        now,
+        checks.max_age_secs,
+        checks.max_conf_ratio_bps,
+    ) {
+        Ok(()) if checks.is_too_new(p.publish_time) => ReadStatus::TooNew,
+        Ok(()) if checks.reject_negative && p.price < 0 => ReadStatus::NegativePrice,
+        Ok(()) if checks.max_abs_conf > 0 && p.conf > checks.max_abs_conf => ReadStatus::WideConfidence,
+        // The publisher count can't be checked, see `check_observation`
+        Ok(()) if checks.min_publishers > 0 => ReadStatus::InvalidArgs,
+        // Without the cache there is nothing to compare with, as on the other cacheless reads
+        Ok(()) if args.max_deviation_bps != 0 => ReadStatus::InvalidArgs,
+        Ok(()) => ReadStatus::Ok,
+        Err(ValidationError::PriceTooOld | ValidationError::FuturePrice) => ReadStatus::Stale,
+        // A zero max age leaves no fresh window at all, whatever the update holds
+        Err(ValidationError::InvalidArgs) => ReadStatus::InvalidArgs,
+        Err(ValidationError::ZeroPrice) => ReadStatus::ZeroPrice,
+        Err(ValidationError::WideConfidence | ValidationError::MathOverflow) => ReadStatus::WideConfidence,
+    };
+
+    SimulatedRead {
+        status,
+        price: Some(ValidatedPrice::new(feed_id, &p)),
+    }
+}
+
+/// `load_validated_price` with the freshness window also capped at `max_age_slots`, converted to
+/// seconds at `MS_PER_SLOT`. The stricter of the two windows applies, so a config or args age limit
+/// is never loosened. Fails with `PriceTooOld` like the seconds-only path.
+fn get_price_no_older_than_slots(
+    price_update: impl Into<AnyPriceUpdate>,
+    feed_id: &FeedId,
+    max_age_slots: u64,
+    checks: &PriceChecks,
+) -> Result<Price> {
+    require!(max_age_slots > 0, ErrorCode::InvalidArgs);
+    let slot_window = slots_to_secs(max_age_slots);
+    let checks = PriceChecks {
+        max_age_secs: checks.max_age_secs.min(slot_window),
+        ..*checks
+    };
+    load_validated_price(price_update, feed_id, &checks).inspect_err(|_| {
+        msg!(
+            "max_age_slots={} taken as {}s at {}ms/slot; effective max age {}s",
+            max_age_slots,
+            slot_window,
+            MS_PER_SLOT,
+            checks.max_age_secs
+        )
+    })
+}
+
+/// Feed `i` of a multi-feed instruction, validated, or None when it is merely older than the
+/// freshness window. Every other failure is still an error, logged with the feed's index.
+fn load_unless_stale(
+    info: &AccountInfo,
+    feed_id: &FeedId,
+    checks: &PriceChecks,
+    i: usize,
+) -> Result<Option<Price>> {
+    match AnyPriceUpdate::try_from_account(info)
+        .and_then(|price_update| load_validated_price(price_update, feed_id, checks))
+    {
+        Ok(p) => Ok(Some(p)),
+        Err(e) if e == Error::from(ErrorCode::PriceTooOld) => {
+            msg!("feed {} stale", i);
+            Ok(None)
+        }
+        Err(e) => {
+            msg!("feed {} failed validation", i);
+            Err(e)
+        }
+    }
+}
+
+/// `info` as the existing, writable `PriceCache` PDA of `feed_id`.
+fn writable_cache<'info>(
+    info: &'info AccountInfo<'info>,
+    feed_id: &FeedId,
+) -> Result<Account<'info, PriceCache>> {
+    require!(info.is_writable, anchor_lang::error::ErrorCode::ConstraintMut);
+    let cache = Account::<PriceCache>::try_from(info)?;
+    // The stored bump makes this a single hash, unlike the search in find_program_address
+    let expected = Pubkey::create_program_address(&[b"cache", feed_id.as_ref(), &[cache.bump]], &crate::ID)
+        .map_err(|_| error!(anchor_lang::error::ErrorCode::ConstraintSeeds))?;
+    require_keys_eq!(info.key(), expected, anchor_lang::error::ErrorCode::ConstraintSeeds);
+    Ok(cache)
+}
+
+/// Record `p` in feed `i`'s cache, and return whether it was written. An observation no newer than
+/// the cached one is skipped like a stale feed, so a keeper reposting an update can't roll the cache
+/// back, nor fail the rest of its batch.
+fn write_cache<'info>(info: &'info AccountInfo<'info>, feed_id: &FeedId, p: &Price, i: usize) -> Result<bool> {
+    let mut cache = writable_cache(info, feed_id).inspect_err(|_| msg!("feed {} has a bad cache", i))?;
+    let bump = cache.bump;
+    match cache.record(*feed_id, p, bump) {
+        Ok(()) => {}
+        Err(e) if e == Error::from(ErrorCode::PriceNotNewerThanCache) => {
+            msg!("feed {} not newer than its cache", i);
+            return Ok(false);
+        }
+        Err(e) => return Err(e),
+    }
+    cache.exit(&crate::ID)?;
+    Ok(true)
+}
+
+/// Reject `p` if it moved more than `max_deviation_bps` away from the cached observation. A limit of
+/// 0 skips the check; any other limit needs the feed's cache account, and only a cache that was
+/// never created or written lets the first observation through.
+fn check_deviation(
+    price_cache: Option<&AccountInfo>,
+    feed_id: &FeedId,
+    p: &Price,
+    max_deviation_bps: u64,
+) -> Result<()> {
+    if max_deviation_bps == 0 {
+        return Ok(());
+    }
+    // Leaving the account out must not be a way around the limit
+    let Some(price_cache) = price_cache else {
+        return err!(ErrorCode::MissingPriceCache);
+    };
+    // Derived here rather than by a seeds constraint, which would need the decoded update; without
+    // it any empty account would pass for a cache that was never created
+    let (expected, _) = Pubkey::find_program_address(&[b"cache", feed_id.as_ref()], &crate::ID);
+    require_keys_eq!(
+        price_cache.key(),
+        expected,
+        anchor_lang::error::ErrorCode::ConstraintSeeds
+    );
+    if price_cache.data_is_empty() {
+        return Ok(());
+    }
+    require_keys_eq!(
+        *price_cache.owner,
+        crate::ID,
+        anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram
+    );
+    let cache = PriceCache::try_deserialize(&mut &price_cache.try_borrow_data()?[..])?;
+    check_cache_deviation(&cache, p, max_deviation_bps)
+}
+
+/// `check_deviation` against a cache the instruction already holds, e.g. the one it is about to
+/// write. A cache that was never written, or a limit of 0, lets `p` through.
+fn check_cache_deviation(cache: &PriceCache, p: &Price, max_deviation_bps: u64) -> Result<()> {
+    if max_deviation_bps == 0 || !cache.is_recorded() {
+        return Ok(());
+    }
+
+    // bps of two prices are only comparable at the same exponent
+    require!(cache.exponent == p.exponent, ErrorCode::ExponentChanged);
+    let deviation = deviation_bps(p.price, cache.price)?;
+    if deviation > u128::from(max_deviation_bps) {
+        msg!(
+            "price={} deviates {} bps from cached={} (t={})",
+            p.price,
+            deviation,
+            cache.price,
+            cache.publish_time
+        );
+        return err!(ErrorCode::PriceDeviationTooLarge);
+    }
+    Ok(())
+}
+
+/// Reject a spot price more than `max_divergence_bps` away from its EMA, and return the divergence.
+fn check_divergence(spot: &Price, ema: &Price, max_divergence_bps: u64) -> Result<u128> {
+    // Spot and EMA come from one message, so they share an exponent
+    let divergence = deviation_bps(spot.price, ema.price)?;
+    if divergence > u128::from(max_divergence_bps) {
+        msg!(
+            "price={} diverges {} bps from ema_price={}",
+            spot.price,
+            divergence,
+            ema.price
+        );
+        return err!(ErrorCode::SpotEmaDivergence);
+    }
+    Ok(divergence)
+}
+
+/// Enforce freshness and the confidence caps, and return the observation.
+fn load_validated_price(
+    price_update: impl Into<AnyPriceUpdate>,
+    feed_id: &FeedId,
+    checks: &PriceChecks,
+) -> Result<Price> {
+    let p = load_price(&price_update.into(), feed_id)?;
+    check_observation(&p, checks)?;
+    Ok(p)
+}
+
+/// Same checks as `load_validated_price`, applied to the EMA price/conf of the update.
+fn load_validated_ema_price(
+    price_update: impl Into<AnyPriceUpdate>,
+    feed_id: &FeedId,
+    checks: &PriceChecks,
+) -> Result<Price> {
+    let price_update = price_update.into();
+    // Same verification as the spot price; the EMA shares its publish_time
+    load_price(&price_update, feed_id)?;
+    let ema = price_update.get_ema_price_unchecked(feed_id)?;
+    check_observation(&ema, checks)?;
+    Ok(ema)
+}
+
+/// Reject the all-zero id an unset `[u8; 32]` defaults to, before the SDK reports a vaguer mismatch.
+fn check_feed_id(feed_id: &FeedId) -> Result<()> {
+    require!(*feed_id != [0u8; 32], ErrorCode::EmptyFeedId);
+    Ok(())
+}
+
+/// Verify the update is for `feed_id` and fully verified; freshness is left to the caller.
+fn load_price(price_update: &AnyPriceUpdate, feed_id: &FeedId) -> Result<Price> {
+    check_feed_id(feed_id)?;
+    require!(
+        price_update.verification_level().gte(VerificationLevel::Full),
+        GetPriceError::InsufficientVerificationLevel
+    );
+    // Checked here so the usual wiring mistake (the BTC account for an ETH read) gets its own error
+    // and both ids in the log, rather than the SDK's generic mismatch
+    let actual = price_update.feed_id();
+    if actual != *feed_id {
+        msg!("feed id mismatch: expected 0x{}, account has 0x{}", hex(feed_id), hex(&actual));
+        return err!(ErrorCode::FeedIdMismatch);
+    }
+    price_update.get_price_unchecked(feed_id)
+}
+
+/// Lowercase hex of `bytes`, for logs.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Unix time `now` reports instead of the cluster clock; unit tests only, never in the program.
+    static NOW_OVERRIDE: core::cell::Cell<Option<i64>> = const { core::cell::Cell::new(None) };
+}
+
+/// Cluster unix time that freshness is judged against. Unit tests can pin it with `NOW_OVERRIDE`,
+/// which does not exist outside `cfg(test)`.
+fn now() -> Result<i64> {
+    #[cfg(test)]
+    if let Some(now) = NOW_OVERRIDE.with(|n| n.get()) {
+        return Ok(now);
+    }
+    Ok(Clock::get()?.unix_timestamp)
+}
+
+/// Apply `validate_observation` against the cluster clock, then the optional caps.
+fn check_observation(p: &Price, checks: &PriceChecks) -> Result<()> {
+    let now = now()?;
+    validate_observation(
+        p.price,
+        p.conf,
+        p.exponent,
+        p.publish_time,
+        now,
+        checks.max_age_secs,
+        checks.max_conf_ratio_bps,
+    )
+    // The error code alone doesn't say by how much the observation missed
+    .inspect_err(|e| match e {
+        ValidationError::PriceTooOld => msg!(
+            "price too old: age={}s, max_age={}s",
+            observation_age(p.publish_time, now).unwrap_or(u64::MAX),
+            checks.max_age_secs
+        ),
+        ValidationError::WideConfidence => msg!(
+            "confidence too wide: ratio={}bps, max={}bps",
+            conf_ratio_bps(p.conf, p.price).unwrap_or(u128::MAX),
+            checks.max_conf_ratio_bps
+        ),
+        _ => {}
+    })?;
+
+    if checks.is_too_new(p.publish_time) {
+        msg!("price too new: t={}, max_publish_time={}", p.publish_time, checks.max_publish_time);
+        return err!(ErrorCode::PriceTooNew);
+    }
+
+    // Zero was already rejected above, whatever the sign setting
+    if checks.reject_negative {
+        require!(p.price > 0, ErrorCode::NegativePrice);
+    }
+
+    // A small ratio can still be a large spread in absolute terms for low-priced assets
+    if checks.max_abs_conf > 0 {
+        require!(p.conf <= checks.max_abs_conf, ErrorCode::ConfidenceTooWide);
+    }
+
+    // PriceFeedMessage carries no publisher count, so a requested minimum can never be verified.
+    // Fail closed rather than silently accept a thin feed.
+    if checks.min_publishers > 0 {
+        msg!(
+            "min_publishers={} requested, but PriceUpdateV2 has no publisher count",
+            checks.min_publishers
+        );
+        return err!(ErrorCode::TooFewPublishers);
+    }
+    Ok(())
+}
+
+/// Observation returned by `read_price` after all checks passed.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct ValidatedPrice {
+    pub feed_id: [u8; 32],
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+}
+
+impl ValidatedPrice {
+    fn new(feed_id: FeedId, p: &Price) -> Self {
+        ValidatedPrice {
+            feed_id,
+            price: p.price,
+            conf: p.conf,
+            exponent: p.exponent,
+            publish_time: p.publish_time,
+        }
+    }
+}
+
+/// Returned by `read_price_full`: the raw observation and the same price as a fixed-point integer.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct FullPrice {
+    pub feed_id: [u8; 32],
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+    /// `price * 10^exponent` at `decimals`, from `scale_price` rounding toward zero
+    pub value_fixed: i128,
+    pub decimals: u32,
+}
+
+impl FullPrice {
+    fn new(feed_id: FeedId, p: &Price, target_decimals: u32) -> Result<Self> {
+        Ok(FullPrice {
+            feed_id,
+            price: p.price,
+            conf: p.conf,
+            exponent: p.exponent,
+            publish_time: p.publish_time,
+            value_fixed: scale_price(p.price, p.exponent, target_decimals, RoundingMode::TowardZero)?,
+            decimals: target_decimals,
+        })
+    }
+}
+
+/// Return data of `read_price` and every other single-price read: `RETURN_DATA_VERSION`, then the
+/// price in that version's layout.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct VersionedPrice {
+    pub version: u8,
+    pub price: ValidatedPrice,
+}
+
+impl VersionedPrice {
+    fn new(price: ValidatedPrice) -> Self {
+        VersionedPrice {
+            version: RETURN_DATA_VERSION,
+            price,
+        }
+    }
+}
+
+/// Return data of `read_price_full`, versioned like `VersionedPrice`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct VersionedFullPrice {
+    pub version: u8,
+    pub price: FullPrice,
+}
+
+impl VersionedFullPrice {
+    fn new(price: FullPrice) -> Self {
+        VersionedFullPrice {
+            version: RETURN_DATA_VERSION,
+            price,
+        }
+    }
+}
+
+/// Return data of `read_price_or_cached`, versioned like `VersionedPrice`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct VersionedMaybeStalePrice {
+    pub version: u8,
+    pub read: MaybeStalePrice,
+}
+
+impl VersionedMaybeStalePrice {
+    fn new(read: MaybeStalePrice) -> Self {
+        VersionedMaybeStalePrice {
+            version: RETURN_DATA_VERSION,
+            read,
+        }
+    }
+}
+
+/// Returned by `cross_price`: base in units of quote, fixed-point with `decimals`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct ValidatedCrossRate {
+    pub base_feed_id: [u8; 32],
+    pub quote_feed_id: [u8; 32],
+    pub ratio: i128,
+    pub decimals: u32,
+    pub base_publish_time: i64,
+    pub quote_publish_time: i64,
+}
+
+/// Return data of `cross_price`, versioned like `VersionedPrice`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct VersionedCrossRate {
+    pub version: u8,
+    pub rate: ValidatedCrossRate,
+}
+
+impl VersionedCrossRate {
+    fn new(rate: ValidatedCrossRate) -> Self {
+        VersionedCrossRate {
+            version: RETURN_DATA_VERSION,
+            rate,
+        }
+    }
+}
+
+/// Decode the return data of any read returning a `VersionedPrice` (`read_price`, `read_by_symbol`,
+/// the range, slippage, EMA and slot-bound reads, `read_price_batch_same_feed` and
+/// `read_price_signed_by_authority`), e.g. from `get_return_data` after a CPI. Data from any version
+/// other than `RETURN_DATA_VERSION` is rejected rather than parsed with the wrong layout.
+pub fn decode_validated_price(data: &[u8]) -> Result<ValidatedPrice> {
+    decode_versioned(data)
+}
+
+/// Decode `cross_price` return data, checked like `decode_validated_price`.
+pub fn decode_cross_rate(data: &[u8]) -> Result<ValidatedCrossRate> {
+    decode_versioned(data)
+}
+
+/// Decode `read_price_full` return data, checked like `decode_validated_price`.
+pub fn decode_full_price(data: &[u8]) -> Result<FullPrice> {
+    decode_versioned(data)
+}
+
+/// Decode `read_price_or_cached` return data, checked like `decode_validated_price`.
+pub fn decode_maybe_stale_price(data: &[u8]) -> Result<MaybeStalePrice> {
+    decode_versioned(data)
+}
+
+/// The body after the version byte, which must be `RETURN_DATA_VERSION`.
+fn decode_versioned<T: AnchorDeserialize>(data: &[u8]) -> Result<T> {
+    let Some((&RETURN_DATA_VERSION, body)) = data.split_first() else {
+        return err!(ErrorCode::UnsupportedReturnVersion);
+    };
+    T::try_from_slice(body).map_err(|_| error!(ErrorCode::UnsupportedReturnVersion))
+}
+
+/// Returned by `read_price_or_cached`; `stale` marks a fallback to the `PriceCache` value.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct MaybeStalePrice {
+    pub price: ValidatedPrice,
+    pub stale: bool,
+}
+
+/// Returned by `healthcheck`; thresholds are the defaults when `configured` is false, which only
+/// happens before `initialize_config`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct Health {
+    /// Crate version the program was built from
+    pub version: String,
+    /// `mainnet` or `devnet`, from the build features
+    pub network: String,
+    /// ETH/USD, the feed the bundled clients read; the args always name their own feed
+    pub default_feed_id: [u8; 32],
+    pub configured: bool,
+    pub max_age_secs: u64,
+    pub max_conf_ratio_bps: u64,
+    pub slot: u64,
+}
+
+/// Outcome of `simulate_read`, mirroring the reasons `read_price` would fail.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadStatus {
+    Ok,
+    /// Outside the freshness window, whether too old or ahead of the clock
+    Stale,
+    WideConfidence,
+    ZeroPrice,
+    /// Feed id unparseable, not allowed, or not in the update, or the account is not a usable update
+    BadFeed,
+    /// Below zero with `reject_negative` set
+    NegativePrice,
+    /// Thresholds no update could pass: a zero max age, or a `min_publishers` or `max_deviation_bps`
+    /// that can't be checked
+    InvalidArgs,
+    /// Published after `max_publish_time`
+    TooNew,
+}
+
+/// Returned by `simulate_read`; `price` is set whenever the update could be decoded, even if stale.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct SimulatedRead {
+    pub status: ReadStatus,
+    pub price: Option<ValidatedPrice>,
+}
+
+/// Returned by `basket_price`: the weighted mean as a fixed-point integer with `decimals`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct BasketPrice {
+    pub price: i128,
+    pub decimals: u32,
+}
+
+/// Returned by `aggregate_prices`: the aggregate as a fixed-point integer with `decimals`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct AggregatePrice {
+    pub price: i128,
+    pub decimals: u32,
+}
+
+/// Returned by `read_twap`; `price` is in the feed's native exponent.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct TwapResult {
+    pub feed_id: [u8; 32],
+    pub price: i64,
+    pub exponent: i32,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub num_samples: u16,
+}
+
+/// Per-call validation parameters for `read_price`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ReadPriceArgs {
+    /// Raw Pyth feed ID; all zeroes falls back to `feed_id_hex`
+    pub feed_id: [u8; 32],
+    /// Pyth feed ID as hex (with or without `0x`), only read when `feed_id` is zero. Leaving both
+    /// empty fails with `EmptyFeedId`; there is no default feed
+    pub feed_id_hex: String,
+    /// Freshness threshold in seconds; must be > 0. The config's window applies when it is tighter
+    pub max_age_secs: u64,
+    /// conf/price cap in basis points (e.g. 200 = 2%); 0 disables the ratio check. The config's cap
+    /// applies when it is tighter
+    pub max_conf_ratio_bps: u64,
+    /// Absolute cap on conf in the feed's native units; 0 disables it
+    pub max_abs_conf: u64,
+    /// Minimum contributing publishers; must be 0 until the receiver exposes the count
+    pub min_publishers: u32,
+    /// Largest move from the `PriceCache` value accepted, in bps; 0 disables it. Checked by
+    /// `read_price`/`read_price_full` (which then require the `price_cache` account), the cache writes
+    /// and `read_price_or_cached`; every other instruction rejects a nonzero value as `InvalidArgs`
+    pub max_deviation_bps: u64,
+    /// Log the observation on success in `read_price`; the event and return data are unaffected
+    pub verbose: bool,
+    /// Fail with `NegativePrice` below zero; clients set it for asset feeds, and clear it only for
+    /// feeds (such as spreads) that can legitimately go negative
+    pub reject_negative: bool,
+    /// Latest publish_time accepted, e.g. to match an off-chain snapshot; with `max_age_secs` it pins
+    /// the read to a window. 0 disables it
+    pub max_publish_time: i64,
+}
+
+impl ReadPriceArgs {
+    /// Refuse a deviation limit on an instruction with no price cache to compare against, rather
+    /// than read without the circuit breaker the caller asked for.
+    fn require_no_deviation_limit(&self) -> Result<()> {
+        if self.max_deviation_bps != 0 {
+            msg!("max_deviation_bps={} set, but there is no price cache to compare with", self.max_deviation_bps);
+            return err!(ErrorCode::InvalidArgs);
+        }
+        Ok(())
+    }
+
+    /// `feed_id` as is, otherwise `feed_id_hex` decoded. Neither set is the forgotten-id mistake,
+    /// rejected with `EmptyFeedId` rather than read as some default feed.
+    ///
+    /// On-chain callers should set `feed_id` and leave the hex empty: it skips the hex parse and
+    /// saves the string bytes in the instruction data.
+    fn feed_id(&self) -> Result<FeedId> {
+        if self.feed_id != [0u8; 32] {
+            return Ok(self.feed_id);
+        }
+        require!(!self.feed_id_hex.is_empty(), ErrorCode::EmptyFeedId);
+        get_feed_id_from_hex(&self.feed_id_hex).map_err(|_| error!(ErrorCode::BadFeedId))
+    }
+
+    /// Thresholds from the args, capped by `config` once it exists.
+    fn checks_with(&self, config: Option<&OracleConfig>, feed_id: &FeedId) -> PriceChecks {
+        PriceChecks {
+            max_abs_conf: self.max_abs_conf,
+            min_publishers: self.min_publishers,
+            reject_negative: self.reject_negative,
+            max_publish_time: self.max_publish_time,
+            ..PriceChecks::new(self.max_age_secs, self.max_conf_ratio_bps)
+        }
+        .with_config(config, feed_id)
+    }
+}
+
+/// Arguments for `read_prices`; `feed_ids[i]` is the feed expected in `remaining_accounts[i]`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ReadPricesArgs {
+    pub feed_ids: Vec<[u8; 32]>,
+    pub max_age_secs: u64,
+    pub max_conf_ratio_bps: u64,
+    /// Fail with `NegativePrice` on any feed below zero; set it for asset feeds
+    pub reject_negative: bool,
+}
+
+impl ReadPricesArgs {
+    fn checks(&self) -> PriceChecks {
+        PriceChecks::new(self.max_age_secs, self.max_conf_ratio_bps).rejecting_negative(self.reject_negative)
+    }
+}
+
+/// Arguments for `basket_price`; `weights[i]` applies to `feed_ids[i]` in `remaining_accounts[i]`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct BasketPriceArgs {
+    pub feed_ids: Vec<[u8; 32]>,
+    pub weights: Vec<u64>,
+    pub max_age_secs: u64,
+    pub max_conf_ratio_bps: u64,
+    /// Fixed-point decimals of the returned price, at most `math::MAX_TARGET_DECIMALS`
+    pub target_decimals: u32,
+    /// Fail with `NegativePrice` on any feed below zero; set it for asset feeds
+    pub reject_negative: bool,
+}
+
+impl BasketPriceArgs {
+    fn checks(&self) -> PriceChecks {
+        PriceChecks::new(self.max_age_secs, self.max_conf_ratio_bps).rejecting_negative(self.reject_negative)
+    }
+}
+
+/// Arguments for `aggregate_prices`; `feed_ids[i]` is read from `remaining_accounts[i]`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct AggregatePricesArgs {
+    pub feed_ids: Vec<[u8; 32]>,
+    pub mode: AggregateMode,
+    pub max_age_secs: u64,
+    pub max_conf_ratio_bps: u64,
+    /// Fixed-point decimals every feed is scaled to before aggregating, at most
+    /// `math::MAX_TARGET_DECIMALS`
+    pub target_decimals: u32,
+    /// Fail with `NegativePrice` on any feed below zero; set it for asset feeds
+    pub reject_negative: bool,
+}
+
+impl AggregatePricesArgs {
+    fn checks(&self) -> PriceChecks {
+        PriceChecks::new(self.max_age_secs, self.max_conf_ratio_bps).rejecting_negative(self.reject_negative)
+    }
+}
+
+/// Arguments for `cross_price`, which prices base in units of quote (e.g. ETH/BTC).
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CrossPriceArgs {
+    pub base_feed_id: [u8; 32],
+    pub quote_feed_id: [u8; 32],
+    pub max_age_secs: u64,
+    pub max_conf_ratio_bps: u64,
+    /// Fixed-point decimals of the returned ratio, at most `math::MAX_TARGET_DECIMALS`
+    pub target_decimals: u32,
+    /// Fail with `NegativePrice` if either leg is below zero, which would flip the ratio's sign
+    pub reject_negative: bool,
+}
+
+/// Thresholds applied to every observation, however the instruction received them.
+#[derive(Clone, Copy)]
+struct PriceChecks {
+    max_age_secs: u64,
+    /// 0 disables the ratio check
+    max_conf_ratio_bps: u64,
+    /// 0 disables the absolute conf cap
+    max_abs_conf: u64,
+    /// 0 disables the publisher count check
+    min_publishers: u32,
+    reject_negative: bool,
+    /// 0 disables the upper publish_time bound
+    max_publish_time: i64,
+}
+
+impl PriceChecks {
+    /// Freshness and ratio caps only; every optional check disabled, negative prices included.
+    fn new(max_age_secs: u64, max_conf_ratio_bps: u64) -> Self {
+        PriceChecks {
+            max_age_secs,
+            max_conf_ratio_bps,
+            max_abs_conf: 0,
+            min_publishers: 0,
+            reject_negative: false,
+            max_publish_time: 0,
+        }
+    }
+
+    /// Also fail with `NegativePrice` below zero when `reject_negative` is set.
+    fn rejecting_negative(self, reject_negative: bool) -> Self {
+        PriceChecks { reject_negative, ..self }
+    }
+
+    /// Whether `publish_time` is past a nonzero `max_publish_time`.
+    fn is_too_new(&self, publish_time: i64) -> bool {
+        self.max_publish_time != 0 && publish_time > self.max_publish_time
+    }
+
+    /// Cap the freshness and ratio thresholds at the ones in `config`, taking the max age from
+    /// `feed_id`'s override if it has one. A caller can tighten the config this way, never loosen it.
+    fn with_config(self, config: Option<&OracleConfig>, feed_id: &FeedId) -> Self {
+        let Some(config) = config else {
+            return self;
+        };
+        // 0 disables the ratio check, so it is the loosest cap rather than the smallest
+        let max_conf_ratio_bps = match (self.max_conf_ratio_bps, config.max_conf_ratio_bps) {
+            (0, cap) | (cap, 0) => cap,
+            (ours, theirs) => ours.min(theirs),
+        };
+        PriceChecks {
+            max_age_secs: self.max_age_secs.min(config.max_age_for(feed_id)),
+            max_conf_ratio_bps,
+            ..self
+        }
+    }
+}
+
+#[derive(Accounts)]
+pub struct ReadPrice<'info> {
+    /// CHECK: owner pinned to the receiver here; decoded by `AnyPriceUpdate::try_from_account`
+    #[account(owner = PYTH_RECEIVER_PROGRAM_ID @ ErrorCode::InvalidPriceAccountOwner)]
+    pub price_update: UncheckedAccount<'info>,
+    /// CHECK: address pinned by seeds; once initialized, its thresholds cap the ones in the args
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: address pinned by seeds; enforced by `check_allowed` once the allowlist exists
+    #[account(seeds = [b"allowlist"], bump)]
+    pub allowlist: UncheckedAccount<'info>,
+    /// CHECK: `read_price` checks the address against the feed, then compares once it is written;
+    /// required whenever `max_deviation_bps` is set
+    pub price_cache: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+#[instruction(authority: Pubkey)]
+pub struct InitScratch<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + PriceScratch::SIZE,
+        seeds = [b"scratch", authority.as_ref()],
+        bump,
+    )]
+    pub scratch: Account<'info, PriceScratch>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReadPriceInto<'info> {
+    /// CHECK: owner pinned to the receiver here; decoded by `AnyPriceUpdate::try_from_account`
+    #[account(owner = PYTH_RECEIVER_PROGRAM_ID @ ErrorCode::InvalidPriceAccountOwner)]
+    pub price_update: UncheckedAccount<'info>,
+    /// CHECK: address pinned by seeds; once initialized, its thresholds cap the ones in the args
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: address pinned by seeds; enforced by `check_allowed` once the allowlist exists
+    #[account(seeds = [b"allowlist"], bump)]
+    pub allowlist: UncheckedAccount<'info>,
+    /// The seeds tie the address to `authority`, so no other signer can write here
+    #[account(mut, seeds = [b"scratch", authority.key().as_ref()], bump)]
+    pub scratch: Account<'info, PriceScratch>,
+    /// Owner of the scratch account, usually a PDA of the calling program
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + OracleConfig::INIT_SPACE,
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, OracleConfig>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, OracleConfig>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddFeed<'info> {
+    #[account(seeds = [b"config"], bump = config.bump, has_one = admin @ ErrorCode::Unauthorized)]
+    pub config: Account<'info, OracleConfig>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + Allowlist::INIT_SPACE,
+        seeds = [b"allowlist"],
+        bump,
+    )]
+    pub allowlist: Account<'info, Allowlist>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveFeed<'info> {
+    #[account(seeds = [b"config"], bump = config.bump, has_one = admin @ ErrorCode::Unauthorized)]
+    pub config: Account<'info, OracleConfig>,
+    #[account(mut, seeds = [b"allowlist"], bump = allowlist.bump)]
+    pub allowlist: Account<'info, Allowlist>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterSymbol<'info> {
+    #[account(seeds = [b"config"], bump = config.bump, has_one = admin @ ErrorCode::Unauthorized)]
+    pub config: Account<'info, OracleConfig>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + SymbolRegistry::INIT_SPACE,
+        seeds = [b"symbols"],
+        bump,
+    )]
+    pub registry: Account<'info, SymbolRegistry>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UnregisterSymbol<'info> {
+    #[account(seeds = [b"config"], bump = config.bump, has_one = admin @ ErrorCode::Unauthorized)]
+    pub config: Account<'info, OracleConfig>,
+    #[account(mut, seeds = [b"symbols"], bump = registry.bump)]
+    pub registry: Account<'info, SymbolRegistry>,
+    pub admin: Signer<'info>,
+}
 
-declare_id!("DsdwXUef3WNJkJSohC5238hiTqqEMrHkftKhiyWaURdj"); // replace with your program ID
+#[derive(Accounts)]
+pub struct ReadBySymbol<'info> {
+    /// CHECK: owner pinned to the receiver here; decoded by `AnyPriceUpdate::try_from_account`
+    #[account(owner = PYTH_RECEIVER_PROGRAM_ID @ ErrorCode::InvalidPriceAccountOwner)]
+    pub price_update: UncheckedAccount<'info>,
+    #[account(seeds = [b"symbols"], bump = registry.bump)]
+    pub registry: Account<'info, SymbolRegistry>,
+    /// CHECK: address pinned by seeds; once initialized, its thresholds cap the ones in the args
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: address pinned by seeds; enforced by `check_allowed` once the allowlist exists
+    #[account(seeds = [b"allowlist"], bump)]
+    pub allowlist: UncheckedAccount<'info>,
+}
 
-const MAX_AGE_SECS: u64 = 60; // freshness threshold
-const FEED_ID_HEX: &str = "0xff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace"; // e.g., ETH/USD feed ID (hex)
-const MAX_CONF_RATIO_BPS: u64 = 200; // 2% conf/price cap (optional)
+#[derive(Accounts)]
+pub struct CloseCache<'info> {
+    #[account(seeds = [b"config"], bump = config.bump, has_one = admin @ ErrorCode::Unauthorized)]
+    pub config: Account<'info, OracleConfig>,
+    #[account(
+        mut,
+        close = receiver,
+        seeds = [b"cache", price_cache.feed_id.as_ref()],
+        bump = price_cache.bump,
+    )]
+    pub price_cache: Account<'info, PriceCache>,
+    /// CHECK: only credited with the cache's lamports
+    #[account(mut)]
+    pub receiver: UncheckedAccount<'info>,
+    pub admin: Signer<'info>,
+}
 
-#[program]
-pub mod pyth_demo {
-    use super::*;
+#[derive(Accounts)]
+pub struct CrossPrice<'info> {
+    /// CHECK: owner pinned to the receiver here; decoded by `AnyPriceUpdate::try_from_account`
+    #[account(owner = PYTH_RECEIVER_PROGRAM_ID @ ErrorCode::InvalidPriceAccountOwner)]
+    pub base_price_update: UncheckedAccount<'info>,
+    /// CHECK: owner pinned to the receiver here; decoded by `AnyPriceUpdate::try_from_account`
+    #[account(owner = PYTH_RECEIVER_PROGRAM_ID @ ErrorCode::InvalidPriceAccountOwner)]
+    pub quote_price_update: UncheckedAccount<'info>,
+    /// CHECK: address pinned by seeds; once initialized, its thresholds cap the ones in the args
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: address pinned by seeds; enforced by `check_allowed` once the allowlist exists
+    #[account(seeds = [b"allowlist"], bump)]
+    pub allowlist: UncheckedAccount<'info>,
+}
 
-    pub fn read_price(ctx: Context<ReadPrice>) -> Result<()> {
-        // Verify we are reading the intended asset
-        let feed_id = get_feed_id_from_hex(FEED_ID_HEX)
-            .map_err(|_| error!(ErrorCode::BadFeedId))?;
+#[derive(Accounts)]
+pub struct Healthcheck<'info> {
+    /// CHECK: address pinned by seeds; the defaults are reported only until it is initialized
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+}
 
-        // Enforce freshness and load the latest observation for that feed
-        let p = ctx.accounts.price_update.get_price_no_older_than(
-            &Clock::get()?, MAX_AGE_SECS, &feed_id
-        )?;
+#[derive(Accounts)]
+pub struct SimulateRead<'info> {
+    /// CHECK: owner and layout are checked in `classify_read`, which reports failures as a status
+    pub price_update: UncheckedAccount<'info>,
+    /// CHECK: address pinned by seeds; once initialized, its thresholds cap the ones in the args
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: address pinned by seeds; enforced by `check_allowed` once the allowlist exists
+    #[account(seeds = [b"allowlist"], bump)]
+    pub allowlist: UncheckedAccount<'info>,
+}
 
-        // Optional confidence bound: reject overly-uncertain prints
-        require!(p.price != 0, ErrorCode::ZeroPrice);
-        let abs_price: u128 = p.price.unsigned_abs() as u128;
-        if abs_price > 0 {
-            // do math in u128 to avoid u64/u128 divide errors
-            let conf_ratio_bps: u128 = (u128::from(p.conf) * 10_000) / abs_price;
-            require!(
-                conf_ratio_bps <= u128::from(MAX_CONF_RATIO_BPS),
-                ErrorCode::WideConfidence
-            );
+#[derive(Accounts)]
+#[instruction(args: ReadPriceArgs)]
+pub struct ReadPriceOrCached<'info> {
+    /// CHECK: owner pinned to the receiver here; decoded by `AnyPriceUpdate::try_from_account`
+    #[account(owner = PYTH_RECEIVER_PROGRAM_ID @ ErrorCode::InvalidPriceAccountOwner)]
+    pub price_update: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"cache", args.feed_id()?.as_ref()],
+        bump = price_cache.bump,
+    )]
+    pub price_cache: Account<'info, PriceCache>,
+    /// CHECK: address pinned by seeds; once initialized, its thresholds cap the ones in the args
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: address pinned by seeds; enforced by `check_allowed` once the allowlist exists
+    #[account(seeds = [b"allowlist"], bump)]
+    pub allowlist: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(feed_id: [u8; 32], capacity: u16)]
+pub struct InitTwap<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = TwapState::space(capacity),
+        seeds = [b"twap", feed_id.as_ref()],
+        bump,
+    )]
+    pub twap_state: Account<'info, TwapState>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(args: ReadPriceArgs)]
+pub struct RecordSample<'info> {
+    /// CHECK: owner pinned to the receiver here; decoded by `AnyPriceUpdate::try_from_account`
+    #[account(owner = PYTH_RECEIVER_PROGRAM_ID @ ErrorCode::InvalidPriceAccountOwner)]
+    pub price_update: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"twap", args.feed_id()?.as_ref()],
+        bump = twap_state.bump,
+    )]
+    pub twap_state: Account<'info, TwapState>,
+    /// CHECK: address pinned by seeds; once initialized, its thresholds cap the ones in the args
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: address pinned by seeds; enforced by `check_allowed` once the allowlist exists
+    #[account(seeds = [b"allowlist"], bump)]
+    pub allowlist: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReadTwap<'info> {
+    pub twap_state: Account<'info, TwapState>,
+}
+
+/// Price update accounts are passed via `remaining_accounts`.
+#[derive(Accounts)]
+pub struct ReadPrices<'info> {
+    /// CHECK: address pinned by seeds; once initialized, its thresholds cap the ones in the args
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: address pinned by seeds; enforced by `check_allowed` once the allowlist exists
+    #[account(seeds = [b"allowlist"], bump)]
+    pub allowlist: UncheckedAccount<'info>,
+}
+
+/// `(price_update, price_cache)` pairs are passed via `remaining_accounts`.
+#[derive(Accounts)]
+pub struct CachePrices<'info> {
+    /// CHECK: address pinned by seeds; once initialized, its thresholds cap the ones in the args
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: address pinned by seeds; enforced by `check_allowed` once the allowlist exists
+    #[account(seeds = [b"allowlist"], bump)]
+    pub allowlist: UncheckedAccount<'info>,
+    /// Must be `config.cache_writer` when one is set
+    pub writer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(feed_id: [u8; 32])]
+pub struct InitCache<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + PriceCache::SIZE,
+        seeds = [b"cache", feed_id.as_ref()],
+        bump,
+    )]
+    pub price_cache: Account<'info, PriceCache>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(args: ReadPriceArgs)]
+pub struct CachePrice<'info> {
+    /// CHECK: owner pinned to the receiver here; decoded by `AnyPriceUpdate::try_from_account`
+    #[account(owner = PYTH_RECEIVER_PROGRAM_ID @ ErrorCode::InvalidPriceAccountOwner)]
+    pub price_update: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + PriceCache::SIZE,
+        seeds = [b"cache", args.feed_id()?.as_ref()],
+        bump,
+    )]
+    pub price_cache: Account<'info, PriceCache>,
+    /// CHECK: address pinned by seeds; once initialized, its thresholds cap the ones in the args
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: address pinned by seeds; enforced by `check_allowed` once the allowlist exists
+    #[account(seeds = [b"allowlist"], bump)]
+    pub allowlist: UncheckedAccount<'info>,
+    /// Also the writer, which must be `config.cache_writer` when one is set
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(args: ReadPriceArgs)]
+pub struct CachePriceSigned<'info> {
+    /// CHECK: owner pinned to the receiver here; decoded by `AnyPriceUpdate::try_from_account`
+    #[account(owner = PYTH_RECEIVER_PROGRAM_ID @ ErrorCode::InvalidPriceAccountOwner)]
+    pub price_update: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + PriceCache::SIZE,
+        seeds = [b"cache", args.feed_id()?.as_ref()],
+        bump,
+    )]
+    pub price_cache: Account<'info, PriceCache>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, OracleConfig>,
+    /// CHECK: address pinned by seeds; enforced by `check_allowed` once the allowlist exists
+    #[account(seeds = [b"allowlist"], bump)]
+    pub allowlist: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Last validated observation for one feed, stored at `["cache", feed_id]`.
+#[account]
+pub struct PriceCache {
+    pub feed_id: [u8; 32],
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+    pub bump: u8,
+}
+
+impl PriceCache {
+    /// Serialized size after the discriminator.
+    pub const SIZE: usize = 32 + 8 + 8 + 4 + 8 + 1;
+
+    /// Whether an observation was ever written; `init_cache` leaves the account empty.
+    pub fn is_recorded(&self) -> bool {
+        self.publish_time != 0
+    }
+
+    /// Overwrite with a validated observation; the PDA may have just been created. Only a strictly
+    /// newer observation is taken, so nobody can roll the cache back by posting an older update.
+    fn record(&mut self, feed_id: FeedId, p: &Price, bump: u8) -> Result<()> {
+        if p.publish_time <= self.publish_time {
+            msg!("cache holds t={}, update has t={}", self.publish_time, p.publish_time);
+            return err!(ErrorCode::PriceNotNewerThanCache);
         }
+        self.feed_id = feed_id;
+        self.price = p.price;
+        self.conf = p.conf;
+        self.exponent = p.exponent;
+        self.publish_time = p.publish_time;
+        self.bump = bump;
 
-        // Log raw integers for offchain display (scale by 10^exponent offchain)
         msg!(
-            "price={}, conf={}, exponent={}, t={}",
+            "cached price={}, conf={}, exponent={}, t={}",
             p.price,
             p.conf,
             p.exponent,
             p.publish_time
         );
-
         Ok(())
     }
 }
 
-#[derive(Accounts)]
-pub struct ReadPrice<'info> {
-    /// CHECK: Receiver SDK validates that this is a PriceUpdateV2 account
-    pub price_update: Account<'info, PriceUpdateV2>,
+/// Where `read_price_into` writes for `authority`, stored at `["scratch", authority]`.
+#[account]
+pub struct PriceScratch {
+    /// Only this signer may have prices written here
+    pub authority: Pubkey,
+    /// Last price written; all zeroes until the first read
+    pub price: ValidatedPrice,
+}
+
+impl PriceScratch {
+    /// Serialized size after the discriminator.
+    pub const SIZE: usize = 32 + (32 + 8 + 8 + 4 + 8);
+}
+
+/// Program-wide validation thresholds, stored at `["config"]` and managed by `admin`.
+#[account]
+#[derive(InitSpace)]
+pub struct OracleConfig {
+    pub admin: Pubkey,
+    pub max_age_secs: u64,
+    pub max_conf_ratio_bps: u64,
+    pub bump: u8,
+    /// Per-feed replacements for `max_age_secs`, at most one per feed
+    #[max_len(MAX_AGE_OVERRIDES)]
+    pub max_age_overrides: Vec<MaxAgeOverride>,
+    /// Only signer `cache_price` and `cache_prices` accept once set; None leaves them permissionless
+    pub cache_writer: Option<Pubkey>,
+}
+
+impl OracleConfig {
+    /// `feed_id`'s override if it has one, otherwise the global `max_age_secs`.
+    pub fn max_age_for(&self, feed_id: &FeedId) -> u64 {
+        self.max_age_overrides
+            .iter()
+            .find(|o| o.feed_id == *feed_id)
+            .map_or(self.max_age_secs, |o| o.max_age_secs)
+    }
+}
+
+/// A feed whose reads are held to their own freshness window.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct MaxAgeOverride {
+    pub feed_id: [u8; 32],
+    pub max_age_secs: u64,
+}
+
+/// Feed IDs accepted by every read and cache write once created, stored at `["allowlist"]`.
+#[account]
+#[derive(InitSpace)]
+pub struct Allowlist {
+    #[max_len(MAX_ALLOWED_FEEDS)]
+    pub feed_ids: Vec<[u8; 32]>,
+    pub bump: u8,
+}
+
+/// Symbols such as "ETH/USD" and the feed each one reads, stored at `["symbols"]`.
+#[account]
+#[derive(InitSpace)]
+pub struct SymbolRegistry {
+    #[max_len(MAX_SYMBOLS)]
+    pub entries: Vec<SymbolEntry>,
+    pub bump: u8,
+}
+
+impl SymbolRegistry {
+    /// Feed id registered under `symbol`, matched exactly (case included).
+    pub fn feed_id(&self, symbol: &str) -> Result<FeedId> {
+        self.entries
+            .iter()
+            .find(|e| e.symbol == symbol)
+            .map(|e| e.feed_id)
+            .ok_or(error!(ErrorCode::UnknownSymbol))
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct SymbolEntry {
+    #[max_len(MAX_SYMBOL_LEN)]
+    pub symbol: String,
+    pub feed_id: [u8; 32],
+}
+
+/// Ring buffer of validated samples for one feed, stored at `["twap", feed_id]`.
+#[account]
+pub struct TwapState {
+    pub feed_id: [u8; 32],
+    /// Shared by every sample; a feed's exponent does not change
+    pub exponent: i32,
+    pub capacity: u16,
+    /// Slot the next sample is written to once the buffer is full
+    pub head: u16,
+    pub samples: Vec<TwapSample>,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, InitSpace)]
+pub struct TwapSample {
+    pub price: i64,
+    pub publish_time: i64,
+}
+
+impl TwapState {
+    /// Account size, discriminator included, for a buffer of `capacity` samples.
+    pub fn space(capacity: u16) -> usize {
+        8 + 32 + 4 + 2 + 2 + (4 + usize::from(capacity) * TwapSample::INIT_SPACE) + 1
+    }
+
+    fn latest(&self) -> Option<&TwapSample> {
+        let last = if self.head == 0 { self.samples.len() } else { usize::from(self.head) };
+        last.checked_sub(1).map(|i| &self.samples[i])
+    }
+
+    /// Append a sample, overwriting the oldest one once `capacity` is reached.
+    fn push(&mut self, sample: TwapSample) {
+        if self.samples.len() < usize::from(self.capacity) {
+            self.samples.push(sample);
+        } else {
+            self.samples[usize::from(self.head)] = sample;
+        }
+        self.head = (self.head + 1) % self.capacity;
+    }
+
+    /// Samples as `(price, publish_time)`, oldest first.
+    fn chronological(&self) -> Vec<(i64, i64)> {
+        let (newer, older) = self.samples.split_at(usize::from(self.head));
+        older
+            .iter()
+            .chain(newer)
+            .map(|s| (s.price, s.publish_time))
+            .collect()
+    }
+}
+
+/// Emitted by `read_price` after every successful validation.
+#[event]
+pub struct PriceRead {
+    pub feed_id: [u8; 32],
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+    pub slot: u64,
+}
+
+/// Emitted by `value_in_usd`; `value` is fixed-point with `value_decimals`.
+#[event]
+pub struct UsdValue {
+    pub feed_id: [u8; 32],
+    pub amount: u64,
+    pub token_decimals: u8,
+    pub price: i64,
+    pub exponent: i32,
+    pub value: i128,
+    pub value_decimals: u32,
+}
+
+/// Emitted by `cross_price`; `ratio` is fixed-point with `decimals`.
+#[event]
+pub struct CrossRate {
+    pub base_feed_id: [u8; 32],
+    pub quote_feed_id: [u8; 32],
+    pub ratio: i128,
+    pub decimals: u32,
+    pub base_publish_time: i64,
+    pub quote_publish_time: i64,
 }
 
 #[error_code]
@@ -60,4 +2314,1112 @@ pub enum ErrorCode {
     ZeroPrice,
     #[msg("price confidence too wide")]
     WideConfidence,
-}
\ No newline at end of file
+    #[msg("invalid instruction arguments")]
+    InvalidArgs,
+    #[msg("scaled price overflows i128")]
+    ScaleOverflow,
+    #[msg("feed count must be between 1 and 16")]
+    TooManyFeeds,
+    #[msg("signer is not the config admin")]
+    Unauthorized,
+    #[msg("feed ID is not on the allowlist")]
+    FeedNotAllowed,
+    #[msg("allowlist is full")]
+    AllowlistFull,
+    #[msg("price was negative")]
+    NegativePrice,
+    #[msg("not enough samples to average")]
+    NotEnoughSamples,
+    #[msg("feed exponent differs from recorded samples")]
+    ExponentChanged,
+    #[msg("price update is ahead of the cluster clock")]
+    FuturePrice,
+    #[msg("price confidence exceeds the absolute cap")]
+    ConfidenceTooWide,
+    #[msg("publish times are not strictly increasing")]
+    NonMonotonicPrices,
+    #[msg("publisher count is not exposed by PriceUpdateV2; min_publishers must be 0")]
+    TooFewPublishers,
+    #[msg("arithmetic overflow")]
+    MathOverflow,
+    #[msg("account data is not a PriceUpdateV2")]
+    NotAPriceUpdate,
+    #[msg("price deviates too far from the cached price")]
+    PriceDeviationTooLarge,
+    #[msg("price is outside the requested range")]
+    PriceOutOfRange,
+    #[msg("target decimals must be at most 18")]
+    InvalidDecimals,
+    #[msg("basket weights sum to zero")]
+    ZeroWeight,
+    #[msg("price is older than the max age")]
+    PriceTooOld,
+    #[msg("not enough compute budget left for this many feeds")]
+    InsufficientComputeBudget,
+    #[msg("feed id is all zeroes")]
+    EmptyFeedId,
+    #[msg("spot price diverges too far from the EMA price")]
+    SpotEmaDivergence,
+    #[msg("price update account is not owned by the Pyth receiver program")]
+    InvalidPriceAccountOwner,
+    #[msg("price moved beyond the allowed slippage from the expected price")]
+    SlippageExceeded,
+    #[msg("no feeds to aggregate")]
+    NoFeeds,
+    #[msg("return data is not in a layout version this build understands")]
+    UnsupportedReturnVersion,
+    #[msg("config already holds the maximum number of max age overrides")]
+    TooManyOverrides,
+    #[msg("feed has no max age override")]
+    OverrideNotFound,
+    #[msg("symbol is not registered")]
+    UnknownSymbol,
+    #[msg("symbol is longer than the registry allows")]
+    SymbolTooLong,
+    #[msg("symbol registry is full")]
+    RegistryFull,
+    #[msg("price update account is for a different feed")]
+    FeedIdMismatch,
+    #[msg("more feeds were stale than the read allows")]
+    TooManyStaleFeeds,
+    #[msg("price was published after max_publish_time")]
+    PriceTooNew,
+    #[msg("price update is not newer than the cached price")]
+    PriceNotNewerThanCache,
+    #[msg("signer is not the config's cache writer")]
+    UnauthorizedCacheWriter,
+    #[msg("max_deviation_bps is set but the price cache account is missing")]
+    MissingPriceCache,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::make_price_update;
+    use crate::validation::MAX_FUTURE_SKEW_SECS;
+
+    fn twap_state(capacity: u16) -> TwapState {
+        TwapState {
+            feed_id: [0; 32],
+            exponent: -8,
+            capacity,
+            head: 0,
+            samples: Vec::new(),
+            bump: 0,
+        }
+    }
+
+    fn sample(price: i64, publish_time: i64) -> TwapSample {
+        TwapSample { price, publish_time }
+    }
+
+    #[test]
+    fn twap_ring_fills_in_order() {
+        let mut twap = twap_state(3);
+        assert_eq!(twap.latest(), None);
+
+        twap.push(sample(1, 10));
+        twap.push(sample(2, 20));
+        assert_eq!(twap.latest(), Some(&sample(2, 20)));
+        assert_eq!(twap.chronological(), vec![(1, 10), (2, 20)]);
+    }
+
+    #[test]
+    fn twap_ring_wraps_around() {
+        let mut twap = twap_state(3);
+        for i in 1..=5 {
+            twap.push(sample(i, i * 10));
+        }
+
+        // 1 and 2 were overwritten; the oldest surviving sample comes first
+        assert_eq!(twap.samples.len(), 3);
+        assert_eq!(twap.latest(), Some(&sample(5, 50)));
+        assert_eq!(twap.chronological(), vec![(3, 30), (4, 40), (5, 50)]);
+
+        twap.push(sample(6, 60));
+        assert_eq!(twap.head, 0);
+        assert_eq!(twap.latest(), Some(&sample(6, 60)));
+        assert_eq!(twap.chronological(), vec![(4, 40), (5, 50), (6, 60)]);
+    }
+
+    /// ETH/USD at 2541.23456789 ± 1.5, published at 1_760_000_000. Built with our own serializer,
+    /// so decoding it only proves a round trip; `decodes_hand_assembled_account_bytes` checks the layout.
+    fn eth_usd_update_blob() -> Vec<u8> {
+        let feed_id = get_feed_id_from_hex(DEFAULT_FEED_ID_HEX).unwrap();
+        make_price_update(feed_id, 254_123_456_789, 150_000_000, -8, 1_760_000_000)
+    }
+
+    #[test]
+    fn decodes_price_update_blob() {
+        let update = decode_price_update(&eth_usd_update_blob()).unwrap();
+        assert_eq!(update.verification_level, VerificationLevel::Full);
+        assert_eq!(update.price_message.feed_id, get_feed_id_from_hex(DEFAULT_FEED_ID_HEX).unwrap());
+        assert_eq!(update.price_message.price, 254_123_456_789);
+        assert_eq!(update.price_message.exponent, -8);
+        assert_eq!(update.price_message.prev_publish_time, 1_759_999_999);
+    }
+
+    /// Not bytes captured from a cluster: they are assembled field by field from the receiver's
+    /// account layout, independently of `try_serialize`, so a drift in either side shows up here.
+    #[test]
+    fn decodes_hand_assembled_account_bytes() {
+        let feed_id = get_feed_id_from_hex(DEFAULT_FEED_ID_HEX).unwrap();
+        let mut data = vec![34, 241, 35, 99, 157, 126, 244, 205]; // sha256("account:PriceUpdateV2")[..8]
+        data.extend([9; 32]); // write_authority
+        data.push(1); // VerificationLevel::Full; Partial would be 0 then num_signatures
+        data.extend(feed_id);
+        data.extend(254_123_456_789i64.to_le_bytes());
+        data.extend(150_000_000u64.to_le_bytes());
+        data.extend((-8i32).to_le_bytes());
+        data.extend(1_760_000_000i64.to_le_bytes()); // publish_time
+        data.extend(1_759_999_999i64.to_le_bytes()); // prev_publish_time
+        data.extend(253_900_000_000i64.to_le_bytes()); // ema_price
+        data.extend(160_000_000u64.to_le_bytes()); // ema_conf
+        data.extend(412_345_678u64.to_le_bytes()); // posted_slot
+        // the receiver allocates room for a Partial level, one byte more than Full takes
+        data.push(0);
+        assert_eq!(data.len(), PriceUpdateV2::LEN);
+
+        let update = decode_price_update(&data).unwrap();
+        assert_eq!(update.write_authority, Pubkey::new_from_array([9; 32]));
+        assert_eq!(update.verification_level, VerificationLevel::Full);
+        let m = &update.price_message;
+        assert_eq!((m.feed_id, m.price, m.conf, m.exponent), (feed_id, 254_123_456_789, 150_000_000, -8));
+        assert_eq!((m.publish_time, m.prev_publish_time), (1_760_000_000, 1_759_999_999));
+        assert_eq!((m.ema_price, m.ema_conf), (253_900_000_000, 160_000_000));
+        assert_eq!(update.posted_slot, 412_345_678);
+        // and our fixture writes the same bytes up to the fields it stubs, from prev_publish_time on
+        let set_by_fixture = 8 + 32 + 1 + 32 + 8 + 8 + 4 + 8;
+        assert_eq!(eth_usd_update_blob()[..set_by_fixture], data[..set_by_fixture]);
+    }
+
+    #[test]
+    fn decodes_v2_through_the_version_wrapper() {
+        let feed_id = get_feed_id_from_hex(DEFAULT_FEED_ID_HEX).unwrap();
+        let update = AnyPriceUpdate::decode(&eth_usd_update_blob()).unwrap();
+        assert!(matches!(update, AnyPriceUpdate::V2(_)));
+        assert_eq!(update.verification_level(), VerificationLevel::Full);
+
+        let spot = update.get_price_unchecked(&feed_id).unwrap();
+        assert_eq!((spot.price, spot.conf, spot.exponent), (254_123_456_789, 150_000_000, -8));
+        let ema = update.get_ema_price_unchecked(&feed_id).unwrap();
+        // the fixture's EMA repeats the spot price, with the same publish_time
+        assert_eq!((ema.price, ema.conf, ema.publish_time), (254_123_456_789, 150_000_000, 1_760_000_000));
+
+        let clock = Clock {
+            unix_timestamp: 1_760_000_030,
+            ..Clock::default()
+        };
+        assert_eq!(update.get_price_no_older_than(&clock, 30, &feed_id).unwrap().price, spot.price);
+        assert!(update.get_price_no_older_than(&clock, 29, &feed_id).is_err());
+
+        assert!(AnyPriceUpdate::decode(&[0; 8]).is_err());
+    }
+
+    /// Spot of the `eth_usd_update_blob` message, and an EMA that lags it by 8.8 bps.
+    fn spot_and_ema() -> (Price, Price) {
+        let update = AnyPriceUpdate::decode(&eth_usd_update_blob()).unwrap();
+        let feed_id = get_feed_id_from_hex(DEFAULT_FEED_ID_HEX).unwrap();
+        let spot = update.get_price_unchecked(&feed_id).unwrap();
+        let ema = Price {
+            price: 253_900_000_000,
+            conf: 160_000_000,
+            ..spot
+        };
+        (spot, ema)
+    }
+
+    #[test]
+    fn spot_within_the_ema_band_passes() {
+        let (spot, ema) = spot_and_ema();
+        // 254_123_456_789 vs 253_900_000_000 is 8.8 bps, rounded down
+        assert_eq!(check_divergence(&spot, &ema, 9).unwrap(), 8);
+        assert_eq!(check_divergence(&spot, &ema, 8).unwrap(), 8);
+        assert_eq!(check_divergence(&ema, &ema, 1).unwrap(), 0);
+    }
+
+    #[test]
+    fn spot_outside_the_ema_band_is_rejected() {
+        let (spot, ema) = spot_and_ema();
+        let diverged: Error = ErrorCode::SpotEmaDivergence.into();
+        assert_eq!(check_divergence(&spot, &ema, 7).unwrap_err(), diverged);
+
+        // A 10% spike either way against the same EMA
+        let spiked = |price| Price { price, ..spot };
+        assert_eq!(check_divergence(&spiked(279_290_000_000), &ema, 999).unwrap_err(), diverged);
+        assert_eq!(check_divergence(&spiked(228_510_000_000), &ema, 999).unwrap_err(), diverged);
+        assert_eq!(check_divergence(&spiked(279_290_000_000), &ema, 1_000).unwrap(), 1_000);
+        // and no band holds against a zero EMA
+        assert_eq!(
+            check_divergence(&spot, &Price { price: 0, ..ema }, u64::MAX).unwrap_err(),
+            ErrorCode::ZeroPrice.into()
+        );
+    }
+
+    /// Run `f` with `now()` pinned to `now`.
+    fn at<T>(now: i64, f: impl FnOnce() -> T) -> T {
+        NOW_OVERRIDE.with(|n| n.set(Some(now)));
+        let out = f();
+        NOW_OVERRIDE.with(|n| n.set(None));
+        out
+    }
+
+    #[test]
+    fn freshness_boundaries_against_a_pinned_clock() {
+        let update = AnyPriceUpdate::decode(&eth_usd_update_blob()).unwrap();
+        let feed_id = get_feed_id_from_hex(DEFAULT_FEED_ID_HEX).unwrap();
+        let checks = PriceChecks::new(60, 200);
+        let read = |now| {
+            at(now, || load_validated_price(update.clone(), &feed_id, &checks).map(|p| p.price))
+        };
+        let published = 1_760_000_000;
+
+        assert_eq!(read(published).unwrap(), 254_123_456_789);
+        // exactly max_age old still passes, one second more does not
+        assert!(read(published + 60).is_ok());
+        assert_eq!(read(published + 61).unwrap_err(), ErrorCode::PriceTooOld.into());
+        // and the same on the future side of the clock
+        assert!(read(published - MAX_FUTURE_SKEW_SECS).is_ok());
+        assert_eq!(read(published - MAX_FUTURE_SKEW_SECS - 1).unwrap_err(), ErrorCode::FuturePrice.into());
+    }
+
+    #[test]
+    fn fabricated_updates_reach_every_validation_path() {
+        let feed_id = [5; 32];
+        let t = 1_760_000_000;
+        let read = |price, conf, publish_time| {
+            let update = AnyPriceUpdate::decode(&make_price_update(feed_id, price, conf, -8, publish_time))?;
+            at(t, || load_validated_price(update, &feed_id, &PriceChecks::new(60, 200)))
+        };
+
+        let p = read(200_000_000_000, 200_000_000, t).unwrap();
+        assert_eq!((p.price, p.conf, p.exponent, p.publish_time), (200_000_000_000, 200_000_000, -8, t));
+        assert_eq!(read(200_000_000_000, 0, t - 61).unwrap_err(), ErrorCode::PriceTooOld.into());
+        assert_eq!(read(200_000_000_000, 0, t + 11).unwrap_err(), ErrorCode::FuturePrice.into());
+        assert_eq!(read(0, 0, t).unwrap_err(), ErrorCode::ZeroPrice.into());
+        // 2.5% against a 2% cap
+        assert_eq!(read(10_000, 250, t).unwrap_err(), ErrorCode::WideConfidence.into());
+        assert_eq!(make_price_update(feed_id, 1, 0, 0, t).len(), PriceUpdateV2::LEN);
+    }
+
+    fn config_with_overrides(overrides: Vec<MaxAgeOverride>) -> OracleConfig {
+        OracleConfig {
+            admin: Pubkey::new_from_array([1; 32]),
+            max_age_secs: 30,
+            max_conf_ratio_bps: 200,
+            bump: 255,
+            max_age_overrides: overrides,
+            cache_writer: None,
+        }
+    }
+
+    #[test]
+    fn feed_overrides_replace_the_global_max_age() {
+        let update = AnyPriceUpdate::decode(&eth_usd_update_blob()).unwrap();
+        let eth = get_feed_id_from_hex(DEFAULT_FEED_ID_HEX).unwrap();
+        let other = [7; 32];
+        let config = config_with_overrides(vec![MaxAgeOverride {
+            feed_id: eth,
+            max_age_secs: 120,
+        }]);
+
+        // the args ask for no limit at all, so the config's windows are what applies
+        let checks = |feed_id| PriceChecks::new(u64::MAX, 0).with_config(Some(&config), feed_id);
+        assert_eq!(checks(&eth).max_age_secs, 120);
+        assert_eq!(checks(&other).max_age_secs, 30);
+        assert_eq!(checks(&eth).max_conf_ratio_bps, 200);
+        let published = 1_760_000_000;
+        assert!(at(published + 100, || load_validated_price(update.clone(), &eth, &checks(&eth))).is_ok());
+
+        // without the override, the feed is back on the 30s global window
+        let config = config_with_overrides(Vec::new());
+        let checks = PriceChecks::new(u64::MAX, 0).with_config(Some(&config), &eth);
+        assert_eq!(checks.max_age_secs, 30);
+        assert_eq!(
+            at(published + 100, || load_validated_price(update.clone(), &eth, &checks)).unwrap_err(),
+            ErrorCode::PriceTooOld.into()
+        );
+    }
+
+    #[test]
+    fn args_can_tighten_the_config_but_not_loosen_it() {
+        let eth = get_feed_id_from_hex(DEFAULT_FEED_ID_HEX).unwrap();
+        let config = config_with_overrides(vec![MaxAgeOverride {
+            feed_id: eth,
+            max_age_secs: 120,
+        }]);
+        let checks = |max_age_secs, max_conf_ratio_bps| {
+            let checks = PriceChecks::new(max_age_secs, max_conf_ratio_bps).with_config(Some(&config), &eth);
+            (checks.max_age_secs, checks.max_conf_ratio_bps)
+        };
+
+        assert_eq!(checks(5, 50), (5, 50));
+        assert_eq!(checks(u64::MAX, 10_000), (120, 200));
+        // a zero ratio in the args means no cap, not the tightest one
+        assert_eq!(checks(60, 0), (60, 200));
+        // and a config without a ratio cap leaves the args' one
+        let uncapped = OracleConfig {
+            max_conf_ratio_bps: 0,
+            ..config_with_overrides(Vec::new())
+        };
+        assert_eq!(PriceChecks::new(60, 150).with_config(Some(&uncapped), &eth).max_conf_ratio_bps, 150);
+    }
+
+    #[test]
+    fn config_applies_as_soon_as_the_pda_exists() {
+        let update = AnyPriceUpdate::decode(&eth_usd_update_blob()).unwrap();
+        let eth = get_feed_id_from_hex(DEFAULT_FEED_ID_HEX).unwrap();
+        let (pda, _) = Pubkey::find_program_address(&[b"config"], &crate::ID);
+        let mut written = Vec::new();
+        config_with_overrides(vec![MaxAgeOverride {
+            feed_id: [7; 32],
+            max_age_secs: 120,
+        }])
+        .try_serialize(&mut written)
+        .unwrap();
+
+        // A caller asking for a year-long window, 100s after the update was published
+        let read = |mut data: Vec<u8>, owner: Pubkey| {
+            let mut lamports = 1;
+            let info = AccountInfo::new(&pda, false, false, &mut lamports, &mut data, &owner, false, 0);
+            let config = load_config(&info)?;
+            let checks = PriceChecks::new(365 * 24 * 3_600, 0).with_config(config.as_ref(), &eth);
+            at(1_760_000_100, || load_validated_price(update.clone(), &eth, &checks)).map(|p| p.price)
+        };
+
+        // Before initialize_config the args are all there is
+        assert!(read(Vec::new(), anchor_lang::system_program::ID).is_ok());
+        // After it, the 30s global window holds whatever the args say; the override is another feed's
+        assert_eq!(read(written.clone(), crate::ID).unwrap_err(), ErrorCode::PriceTooOld.into());
+        // An account this program did not write is never taken for the config
+        assert_eq!(
+            read(written, Pubkey::new_unique()).unwrap_err(),
+            anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram.into()
+        );
+    }
+
+    #[test]
+    fn negative_prices_are_rejected_only_when_asked() {
+        let published = 1_760_000_000;
+        let check = |price, reject_negative| {
+            let p = Price {
+                price,
+                conf: 0,
+                exponent: -8,
+                publish_time: published,
+            };
+            let checks = PriceChecks {
+                reject_negative,
+                ..PriceChecks::new(60, 0)
+            };
+            at(published, || check_observation(&p, &checks))
+        };
+
+        for reject_negative in [true, false] {
+            assert!(check(254_123_456_789, reject_negative).is_ok());
+            assert!(check(1, reject_negative).is_ok());
+            // zero is its own error either way
+            assert_eq!(check(0, reject_negative).unwrap_err(), ErrorCode::ZeroPrice.into());
+        }
+        assert_eq!(check(-1, true).unwrap_err(), ErrorCode::NegativePrice.into());
+        assert_eq!(check(i64::MIN, true).unwrap_err(), ErrorCode::NegativePrice.into());
+        assert!(check(-1, false).is_ok());
+        assert!(check(i64::MIN, false).is_ok());
+    }
+
+    #[test]
+    fn publish_time_can_be_pinned_to_a_window() {
+        let now = 1_760_000_000;
+        let check = |publish_time, max_age_secs, max_publish_time| {
+            let p = Price {
+                price: 1,
+                conf: 0,
+                exponent: -8,
+                publish_time,
+            };
+            let checks = PriceChecks {
+                max_publish_time,
+                ..PriceChecks::new(max_age_secs, 0)
+            };
+            at(now, || check_observation(&p, &checks))
+        };
+        let too_old: Error = ErrorCode::PriceTooOld.into();
+        let too_new: Error = ErrorCode::PriceTooNew.into();
+
+        // lower bound only: max_age_secs back from the clock
+        assert!(check(now - 60, 60, 0).is_ok());
+        assert_eq!(check(now - 61, 60, 0).unwrap_err(), too_old);
+
+        // upper bound only, with the age window wide open
+        let pin = now - 3_600;
+        assert!(check(pin, u64::MAX, pin).is_ok());
+        assert!(check(pin - 1, u64::MAX, pin).is_ok());
+        assert_eq!(check(pin + 1, u64::MAX, pin).unwrap_err(), too_new);
+        assert_eq!(check(now, u64::MAX, pin).unwrap_err(), too_new);
+
+        // both: [now - 120, pin] accepts only what lies between
+        let window = |publish_time| check(publish_time, 120, now - 30);
+        assert!(window(now - 120).is_ok());
+        assert!(window(now - 30).is_ok());
+        assert_eq!(window(now - 121).unwrap_err(), too_old);
+        assert_eq!(window(now - 29).unwrap_err(), too_new);
+    }
+
+    #[test]
+    fn the_override_is_per_test() {
+        // Nothing pinned: now() goes to the real sysvar, which native unit tests don't have
+        assert!(now().is_err());
+        assert_eq!(at(42, now).unwrap(), 42);
+        assert!(now().is_err());
+    }
+
+    #[test]
+    fn full_price_scales_the_raw_components_it_returns() {
+        let update = AnyPriceUpdate::decode(&eth_usd_update_blob()).unwrap();
+        let feed_id = get_feed_id_from_hex(DEFAULT_FEED_ID_HEX).unwrap();
+        let p = update.get_price_unchecked(&feed_id).unwrap();
+
+        for decimals in [0, 2, 8, 18] {
+            let full = FullPrice::new(feed_id, &p, decimals).unwrap();
+            assert_eq!(
+                (full.feed_id, full.price, full.conf, full.exponent, full.publish_time),
+                (feed_id, p.price, p.conf, p.exponent, p.publish_time)
+            );
+            assert_eq!(full.decimals, decimals);
+            assert_eq!(
+                full.value_fixed,
+                scale_price(full.price, full.exponent, decimals, RoundingMode::TowardZero).unwrap()
+            );
+        }
+        // 2541.23456789 truncated to cents, and widened to 18 decimals exactly
+        assert_eq!(FullPrice::new(feed_id, &p, 2).unwrap().value_fixed, 254_123);
+        assert_eq!(FullPrice::new(feed_id, &p, 18).unwrap().value_fixed, 2_541_234_567_890_000_000_000);
+        assert_eq!(
+            FullPrice::new(feed_id, &p, math::MAX_TARGET_DECIMALS + 1).unwrap_err(),
+            ErrorCode::InvalidDecimals.into()
+        );
+    }
+
+    #[test]
+    fn return_data_round_trips_with_its_version() {
+        let price = ValidatedPrice {
+            feed_id: [3; 32],
+            price: 254_123_456_789,
+            conf: 150_000_000,
+            exponent: -8,
+            publish_time: 1_760_000_000,
+        };
+        let data = VersionedPrice::new(price.clone()).try_to_vec().unwrap();
+        assert_eq!(data[0], RETURN_DATA_VERSION);
+        assert_eq!(decode_validated_price(&data).unwrap(), price);
+
+        // The full and maybe-stale reads share the version byte and its check
+        let full = FullPrice {
+            feed_id: price.feed_id,
+            price: price.price,
+            conf: price.conf,
+            exponent: price.exponent,
+            publish_time: price.publish_time,
+            value_fixed: 254_123,
+            decimals: 2,
+        };
+        let mut data = VersionedFullPrice::new(full.clone()).try_to_vec().unwrap();
+        assert_eq!(data[0], RETURN_DATA_VERSION);
+        assert_eq!(decode_full_price(&data).unwrap(), full);
+        data[0] = RETURN_DATA_VERSION + 1;
+        assert_eq!(decode_full_price(&data).unwrap_err(), ErrorCode::UnsupportedReturnVersion.into());
+
+        let read = MaybeStalePrice { price, stale: true };
+        let mut data = VersionedMaybeStalePrice::new(read.clone()).try_to_vec().unwrap();
+        assert_eq!(data[0], RETURN_DATA_VERSION);
+        assert_eq!(decode_maybe_stale_price(&data).unwrap(), read);
+        data[0] = RETURN_DATA_VERSION + 1;
+        assert_eq!(decode_maybe_stale_price(&data).unwrap_err(), ErrorCode::UnsupportedReturnVersion.into());
+
+        let rate = ValidatedCrossRate {
+            base_feed_id: [3; 32],
+            quote_feed_id: [4; 32],
+            ratio: 38_123_456,
+            decimals: 6,
+            base_publish_time: 1_760_000_000,
+            quote_publish_time: 1_759_999_999,
+        };
+        let mut data = VersionedCrossRate::new(rate.clone()).try_to_vec().unwrap();
+        assert_eq!(data[0], RETURN_DATA_VERSION);
+        assert_eq!(decode_cross_rate(&data).unwrap(), rate);
+        data[0] = RETURN_DATA_VERSION + 1;
+        assert_eq!(decode_cross_rate(&data).unwrap_err(), ErrorCode::UnsupportedReturnVersion.into());
+    }
+
+    #[test]
+    fn rejects_an_unknown_return_version() {
+        let unsupported: Error = ErrorCode::UnsupportedReturnVersion.into();
+        let mut data = VersionedPrice {
+            version: RETURN_DATA_VERSION,
+            price: ValidatedPrice {
+                feed_id: [3; 32],
+                price: 1,
+                conf: 0,
+                exponent: 0,
+                publish_time: 0,
+            },
+        }
+        .try_to_vec()
+        .unwrap();
+
+        data[0] = RETURN_DATA_VERSION + 1;
+        assert_eq!(decode_validated_price(&data).unwrap_err(), unsupported);
+        // pre-versioning data started straight with the feed id
+        assert_eq!(decode_validated_price(&data[1..]).unwrap_err(), unsupported);
+        assert_eq!(decode_validated_price(&[]).unwrap_err(), unsupported);
+        // the right version with a truncated body is no better
+        data[0] = RETURN_DATA_VERSION;
+        assert_eq!(decode_validated_price(&data[..20]).unwrap_err(), unsupported);
+    }
+
+    #[test]
+    fn rejects_an_update_for_another_feed() {
+        let update = AnyPriceUpdate::decode(&eth_usd_update_blob()).unwrap();
+        let btc_usd =
+            get_feed_id_from_hex("0xe62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43").unwrap();
+        assert_eq!(load_price(&update, &btc_usd).unwrap_err(), ErrorCode::FeedIdMismatch.into());
+        // the validated paths go through the same check
+        let checks = PriceChecks::new(60, 200);
+        assert_eq!(
+            load_validated_price(update, &btc_usd, &checks).unwrap_err(),
+            ErrorCode::FeedIdMismatch.into()
+        );
+        assert_eq!(hex(&[0x0a, 0xff]), "0aff");
+    }
+
+    #[test]
+    fn keeper_writes_only_to_the_feed_cache_pda() {
+        let feed_id = [9; 32];
+        let (pda, bump) = Pubkey::find_program_address(&[b"cache", feed_id.as_ref()], &crate::ID);
+        let mut cache_data = Vec::new();
+        PriceCache {
+            feed_id,
+            price: 0,
+            conf: 0,
+            exponent: 0,
+            publish_time: 0,
+            bump,
+        }
+        .try_serialize(&mut cache_data)
+        .unwrap();
+
+        let check = |key: Pubkey, writable: bool| {
+            let (mut lamports, mut data) = (1, cache_data.clone());
+            let info = AccountInfo::new(&key, false, writable, &mut lamports, &mut data, &crate::ID, false, 0);
+            writable_cache(&info, &feed_id).map(|cache| cache.feed_id)
+        };
+        assert_eq!(check(pda, true).unwrap(), feed_id);
+        assert_eq!(
+            check(pda, false).unwrap_err(),
+            anchor_lang::error::ErrorCode::ConstraintMut.into()
+        );
+        // Right layout and owner, but not at this feed's address
+        assert_eq!(
+            check(Pubkey::new_unique(), true).unwrap_err(),
+            anchor_lang::error::ErrorCode::ConstraintSeeds.into()
+        );
+    }
+
+    #[test]
+    fn keeper_batches_never_roll_a_cache_back() {
+        let (spot, _) = spot_and_ema();
+        let feed_id = get_feed_id_from_hex(DEFAULT_FEED_ID_HEX).unwrap();
+        let (pda, bump) = Pubkey::find_program_address(&[b"cache", feed_id.as_ref()], &crate::ID);
+        let mut data = Vec::new();
+        PriceCache {
+            feed_id,
+            price: 0,
+            conf: 0,
+            exponent: 0,
+            publish_time: 0,
+            bump,
+        }
+        .try_serialize(&mut data)
+        .unwrap();
+
+        let mut lamports = 1;
+        let info = AccountInfo::new(&pda, false, true, &mut lamports, &mut data, &crate::ID, false, 0);
+        let cached = || {
+            let cache = PriceCache::try_deserialize(&mut &info.try_borrow_data().unwrap()[..]).unwrap();
+            (cache.price, cache.publish_time)
+        };
+        let at_time = |publish_time, price| Price {
+            price,
+            publish_time,
+            ..spot
+        };
+
+        assert!(write_cache(&info, &feed_id, &spot, 0).unwrap());
+        assert_eq!(cached(), (spot.price, spot.publish_time));
+        // The same update again, or an hour-old one, is skipped and the cache keeps the newest
+        assert!(!write_cache(&info, &feed_id, &at_time(spot.publish_time, 1), 0).unwrap());
+        assert!(!write_cache(&info, &feed_id, &at_time(spot.publish_time - 3_600, 1), 0).unwrap());
+        assert_eq!(cached(), (spot.price, spot.publish_time));
+        assert!(write_cache(&info, &feed_id, &at_time(spot.publish_time + 1, 42), 0).unwrap());
+        assert_eq!(cached(), (42, spot.publish_time + 1));
+    }
+
+    #[test]
+    fn allowlist_gates_every_feed_of_a_batch() {
+        let (pda, bump) = Pubkey::find_program_address(&[b"allowlist"], &crate::ID);
+        let mut listed = Vec::new();
+        Allowlist {
+            feed_ids: vec![[1; 32], [2; 32]],
+            bump,
+        }
+        .try_serialize(&mut listed)
+        .unwrap();
+
+        let check = |mut data: Vec<u8>, owner: Pubkey, feed_ids: &[FeedId]| {
+            let mut lamports = 1;
+            let info = AccountInfo::new(&pda, false, false, &mut lamports, &mut data, &owner, false, 0);
+            check_all_allowed(&info, feed_ids)
+        };
+        let not_allowed: Error = ErrorCode::FeedNotAllowed.into();
+        let owner = crate::ID;
+
+        assert!(check(listed.clone(), owner, &[[1; 32], [2; 32], [1; 32]]).is_ok());
+        // one unlisted feed anywhere in the batch fails all of it
+        assert_eq!(check(listed.clone(), owner, &[[1; 32], [3; 32], [2; 32]]).unwrap_err(), not_allowed);
+        assert_eq!(check(listed.clone(), owner, &[[3; 32]]).unwrap_err(), not_allowed);
+        // before add_feed creates the PDA, anything goes
+        assert!(check(Vec::new(), anchor_lang::system_program::ID, &[[3; 32]]).is_ok());
+        assert_eq!(check(listed, Pubkey::new_unique(), &[[1; 32]]).unwrap_err(), not_allowed);
+    }
+
+    #[test]
+    fn batch_args_reject_negative_prices_only_when_asked() {
+        let feed_id = [5; 32];
+        let t = 1_760_000_000;
+        let load = |price, checks: PriceChecks| {
+            let mut data = make_price_update(feed_id, price, 0, -8, t);
+            let (key, mut lamports) = (Pubkey::new_unique(), 1);
+            let owner = PYTH_RECEIVER_PROGRAM_ID;
+            let info = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+            at(t, || load_unless_stale(&info, &feed_id, &checks, 0).map(|p| p.map(|p| p.price)))
+        };
+        // read_prices and cache_prices share ReadPricesArgs
+        let read = |reject_negative| {
+            ReadPricesArgs {
+                feed_ids: vec![feed_id],
+                max_age_secs: 60,
+                max_conf_ratio_bps: 0,
+                reject_negative,
+            }
+            .checks()
+        };
+        let basket = |reject_negative| {
+            BasketPriceArgs {
+                feed_ids: vec![feed_id],
+                weights: vec![1],
+                max_age_secs: 60,
+                max_conf_ratio_bps: 0,
+                target_decimals: 8,
+                reject_negative,
+            }
+            .checks()
+        };
+        let aggregate = |reject_negative| {
+            AggregatePricesArgs {
+                feed_ids: vec![feed_id],
+                mode: AggregateMode::Median,
+                max_age_secs: 60,
+                max_conf_ratio_bps: 0,
+                target_decimals: 8,
+                reject_negative,
+            }
+            .checks()
+        };
+
+        let paths: [&dyn Fn(bool) -> PriceChecks; 3] = [&read, &basket, &aggregate];
+        for checks in paths {
+            for reject_negative in [true, false] {
+                assert_eq!(load(42, checks(reject_negative)).unwrap(), Some(42));
+                assert_eq!(load(0, checks(reject_negative)).unwrap_err(), ErrorCode::ZeroPrice.into());
+            }
+            assert_eq!(load(-42, checks(true)).unwrap_err(), ErrorCode::NegativePrice.into());
+            assert_eq!(load(-42, checks(false)).unwrap(), Some(-42));
+        }
+    }
+
+    #[test]
+    fn only_a_stale_feed_is_tolerated_in_a_batch() {
+        let feed_id = [5; 32];
+        let t = 1_760_000_000;
+        let load = |price, publish_time| {
+            let mut data = make_price_update(feed_id, price, 0, -8, publish_time);
+            let (key, mut lamports) = (Pubkey::new_unique(), 1);
+            let owner = PYTH_RECEIVER_PROGRAM_ID;
+            let info = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+            let checks = PriceChecks::new(60, 0);
+            at(t, || load_unless_stale(&info, &feed_id, &checks, 0).map(|p| p.map(|p| p.price)))
+        };
+
+        assert_eq!(load(42, t).unwrap(), Some(42));
+        assert_eq!(load(42, t - 61).unwrap(), None);
+        // ahead of the clock is not staleness, and neither is a bad price
+        assert_eq!(load(42, t + 3_600).unwrap_err(), ErrorCode::FuturePrice.into());
+        assert_eq!(load(0, t).unwrap_err(), ErrorCode::ZeroPrice.into());
+    }
+
+    #[test]
+    fn rejects_the_zero_feed_id() {
+        let empty: Error = ErrorCode::EmptyFeedId.into();
+        assert_eq!(check_feed_id(&[0; 32]).unwrap_err(), empty);
+        let mut last_byte_set = [0; 32];
+        last_byte_set[31] = 1;
+        assert!(check_feed_id(&last_byte_set).is_ok());
+
+        // Caught before the SDK's feed id comparison
+        let update = AnyPriceUpdate::decode(&eth_usd_update_blob()).unwrap();
+        assert_eq!(load_price(&update, &[0; 32]).unwrap_err(), empty);
+
+        // A zero raw id falls back to the hex; left empty too, nothing names a feed
+        let args = |feed_id_hex: &str| ReadPriceArgs {
+            feed_id: [0; 32],
+            feed_id_hex: feed_id_hex.to_string(),
+            max_age_secs: 60,
+            max_conf_ratio_bps: 0,
+            max_abs_conf: 0,
+            min_publishers: 0,
+            max_deviation_bps: 0,
+            verbose: false,
+            reject_negative: true,
+            max_publish_time: 0,
+        };
+        assert_eq!(args("").feed_id().unwrap_err(), empty);
+        // and an explicit zero hex id is caught once it reaches load_price
+        let zero_hex = args(&format!("0x{}", "00".repeat(32))).feed_id().unwrap();
+        assert_eq!(load_price(&update, &zero_hex).unwrap_err(), empty);
+        let eth = args(DEFAULT_FEED_ID_HEX).feed_id().unwrap();
+        assert!(load_price(&update, &eth).is_ok());
+    }
+
+    #[test]
+    fn rejects_other_account_data() {
+        // PriceUpdateV2 is not Debug, so compare the error side only
+        let rejects =
+            |data: &[u8]| decode_price_update(data).err() == Some(ErrorCode::NotAPriceUpdate.into());
+        let mut blob = eth_usd_update_blob();
+
+        assert!(rejects(&[]));
+        assert!(rejects(&blob[..40]));
+
+        let mut cache = Vec::new();
+        PriceCache {
+            feed_id: [0; 32],
+            price: 1,
+            conf: 0,
+            exponent: 0,
+            publish_time: 0,
+            bump: 0,
+        }
+        .try_serialize(&mut cache)
+        .unwrap();
+        assert!(rejects(&cache));
+
+        blob[0] ^= 1;
+        assert!(rejects(&blob));
+    }
+
+    #[test]
+    fn cache_only_moves_forward_in_time() {
+        let (spot, _) = spot_and_ema();
+        let feed_id = get_feed_id_from_hex(DEFAULT_FEED_ID_HEX).unwrap();
+        let mut cache = PriceCache {
+            feed_id,
+            price: 0,
+            conf: 0,
+            exponent: 0,
+            publish_time: 0,
+            bump: 255,
+        };
+        let at_time = |publish_time, price| Price {
+            price,
+            publish_time,
+            ..spot
+        };
+        let not_newer: Error = ErrorCode::PriceNotNewerThanCache.into();
+
+        // an empty cache takes anything
+        cache.record(feed_id, &spot, 255).unwrap();
+        assert_eq!((cache.price, cache.publish_time), (spot.price, spot.publish_time));
+
+        // Replaying the same update, or posting an older one, leaves it as it was
+        assert_eq!(cache.record(feed_id, &at_time(spot.publish_time, 1), 255).unwrap_err(), not_newer);
+        assert_eq!(cache.record(feed_id, &at_time(spot.publish_time - 3_600, 1), 255).unwrap_err(), not_newer);
+        assert_eq!((cache.price, cache.publish_time), (spot.price, spot.publish_time));
+
+        cache.record(feed_id, &at_time(spot.publish_time + 1, 42), 255).unwrap();
+        assert_eq!((cache.price, cache.publish_time), (42, spot.publish_time + 1));
+    }
+
+    #[test]
+    fn only_the_cache_writer_may_write_once_set() {
+        let keeper = Pubkey::new_unique();
+        let anyone = Pubkey::new_unique();
+        let denied: Error = ErrorCode::UnauthorizedCacheWriter.into();
+
+        // No config, or one without a writer: permissionless
+        assert!(check_cache_writer(None, &anyone).is_ok());
+        let open = config_with_overrides(Vec::new());
+        assert!(check_cache_writer(Some(&open), &anyone).is_ok());
+
+        let restricted = OracleConfig {
+            cache_writer: Some(keeper),
+            ..config_with_overrides(Vec::new())
+        };
+        assert!(check_cache_writer(Some(&restricted), &keeper).is_ok());
+        assert_eq!(check_cache_writer(Some(&restricted), &anyone).unwrap_err(), denied);
+        // not even the admin, who has read_price_signed_by_authority for that
+        assert_eq!(check_cache_writer(Some(&restricted), &restricted.admin).unwrap_err(), denied);
+    }
+
+    #[test]
+    fn deviation_limit_requires_the_cache_account() {
+        let (spot, _) = spot_and_ema();
+        let feed_id = get_feed_id_from_hex(DEFAULT_FEED_ID_HEX).unwrap();
+        let (pda, _) = Pubkey::find_program_address(&[b"cache", feed_id.as_ref()], &crate::ID);
+        let check = |key: Pubkey, mut data: Vec<u8>, max_deviation_bps| {
+            let mut lamports = 0;
+            let info = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &crate::ID, false, 0);
+            check_deviation(Some(&info), &feed_id, &spot, max_deviation_bps)
+        };
+        let cache_at = |price, publish_time| {
+            let cache = PriceCache {
+                feed_id,
+                price,
+                conf: spot.conf,
+                exponent: spot.exponent,
+                publish_time,
+                bump: 255,
+            };
+            let mut data = Vec::new();
+            cache.try_serialize(&mut data).unwrap();
+            data
+        };
+
+        // Without a limit the account is not needed; with one, omitting it is refused
+        assert!(check_deviation(None, &feed_id, &spot, 0).is_ok());
+        assert_eq!(
+            check_deviation(None, &feed_id, &spot, 100).unwrap_err(),
+            ErrorCode::MissingPriceCache.into()
+        );
+
+        // First observation: the PDA was never created, or created but never written
+        assert!(check(pda, Vec::new(), 100).is_ok());
+        assert!(check(pda, cache_at(0, 0), 100).is_ok());
+        let earlier = spot.publish_time - 1;
+        assert!(check(pda, cache_at(spot.price, earlier), 100).is_ok());
+        assert_eq!(
+            check(pda, cache_at(spot.price * 2, earlier), 100).unwrap_err(),
+            ErrorCode::PriceDeviationTooLarge.into()
+        );
+
+        // Some other empty account cannot stand in for the feed's cache
+        assert_eq!(
+            check(Pubkey::new_unique(), Vec::new(), 100).unwrap_err(),
+            anchor_lang::error::ErrorCode::ConstraintSeeds.into()
+        );
+    }
+
+    #[test]
+    fn simulated_reads_tell_bad_args_from_stale_updates() {
+        let feed_id = [5; 32];
+        let t = 1_760_000_000;
+        let status = |publish_time, args: ReadPriceArgs| {
+            let mut data = make_price_update(feed_id, 42, 0, -8, publish_time);
+            let (mut no_data, mut lamports, mut allowlist_lamports) = (Vec::new(), 1, 0);
+            let (key, allowlist_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+            let owner = PYTH_RECEIVER_PROGRAM_ID;
+            let update = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+            let allowlist = AccountInfo::new(
+                &allowlist_key,
+                false,
+                false,
+                &mut allowlist_lamports,
+                &mut no_data,
+                &crate::ID,
+                false,
+                0,
+            );
+            classify_read(&update, &allowlist, &args, None, t).status
+        };
+        let args = ReadPriceArgs {
+            feed_id,
+            feed_id_hex: String::new(),
+            max_age_secs: 60,
+            max_conf_ratio_bps: 0,
+            max_abs_conf: 0,
+            min_publishers: 0,
+            max_deviation_bps: 0,
+            verbose: false,
+            reject_negative: true,
+            max_publish_time: 0,
+        };
+
+        assert_eq!(status(t, args.clone()), ReadStatus::Ok);
+        assert_eq!(status(t - 61, args.clone()), ReadStatus::Stale);
+        assert_eq!(status(t + 3_600, args.clone()), ReadStatus::Stale);
+        let too_new = ReadPriceArgs {
+            max_publish_time: t - 1,
+            ..args.clone()
+        };
+        assert_eq!(status(t, too_new), ReadStatus::TooNew);
+        // the same fresh update, with thresholds nothing could pass
+        let zero_age = ReadPriceArgs {
+            max_age_secs: 0,
+            ..args.clone()
+        };
+        assert_eq!(status(t, zero_age), ReadStatus::InvalidArgs);
+        let publishers = ReadPriceArgs {
+            min_publishers: 3,
+            ..args.clone()
+        };
+        assert_eq!(status(t, publishers), ReadStatus::InvalidArgs);
+        let deviation = ReadPriceArgs {
+            max_deviation_bps: 100,
+            ..args
+        };
+        assert_eq!(status(t, deviation), ReadStatus::InvalidArgs);
+    }
+
+    #[test]
+    fn deviation_limit_is_refused_where_no_cache_is_taken() {
+        let args = |max_deviation_bps| ReadPriceArgs {
+            feed_id: [5; 32],
+            feed_id_hex: String::new(),
+            max_age_secs: 60,
+            max_conf_ratio_bps: 0,
+            max_abs_conf: 0,
+            min_publishers: 0,
+            max_deviation_bps,
+            verbose: false,
+            reject_negative: true,
+            max_publish_time: 0,
+        };
+        assert!(args(0).require_no_deviation_limit().is_ok());
+        assert_eq!(
+            args(1).require_no_deviation_limit().unwrap_err(),
+            ErrorCode::InvalidArgs.into()
+        );
+    }
+
+    #[test]
+    fn cache_writes_compare_against_the_cache_they_hold() {
+        let (spot, _) = spot_and_ema();
+        let cache_at = |price, publish_time| PriceCache {
+            feed_id: [5; 32],
+            price,
+            conf: spot.conf,
+            exponent: spot.exponent,
+            publish_time,
+            bump: 255,
+        };
+        let deviated: Error = ErrorCode::PriceDeviationTooLarge.into();
+
+        // A cache init_if_needed just created is all zeros: the first observation goes through
+        assert!(check_cache_deviation(&cache_at(0, 0), &spot, 1).is_ok());
+        let earlier = spot.publish_time - 1;
+        assert!(check_cache_deviation(&cache_at(spot.price, earlier), &spot, 1).is_ok());
+        let doubled = cache_at(spot.price * 2, earlier);
+        assert_eq!(check_cache_deviation(&doubled, &spot, 100).unwrap_err(), deviated);
+        // and no limit, no comparison
+        assert!(check_cache_deviation(&doubled, &spot, 0).is_ok());
+        let rescaled = PriceCache {
+            exponent: spot.exponent - 1,
+            ..cache_at(spot.price, earlier)
+        };
+        assert_eq!(
+            check_cache_deviation(&rescaled, &spot, 100).unwrap_err(),
+            ErrorCode::ExponentChanged.into()
+        );
+    }
+
+    #[test]
+    fn cache_size_matches_serialized_size() {
+        let cache = PriceCache {
+            feed_id: [1; 32],
+            price: i64::MIN,
+            conf: u64::MAX,
+            exponent: -8,
+            publish_time: i64::MAX,
+            bump: 255,
+        };
+        assert_eq!(cache.try_to_vec().unwrap().len(), PriceCache::SIZE);
+    }
+
+    #[test]
+    fn config_space_fits_every_override() {
+        let overrides = (0..MAX_AGE_OVERRIDES as u8)
+            .map(|i| MaxAgeOverride {
+                feed_id: [i; 32],
+                max_age_secs: u64::MAX,
+            })
+            .collect();
+        let config = OracleConfig {
+            cache_writer: Some(Pubkey::new_from_array([2; 32])),
+            ..config_with_overrides(overrides)
+        };
+        assert_eq!(config.try_to_vec().unwrap().len(), OracleConfig::INIT_SPACE);
+    }
+
+    #[test]
+    fn registry_space_fits_every_symbol_at_full_length() {
+        let entries = (0..MAX_SYMBOLS as u8)
+            .map(|i| SymbolEntry {
+                symbol: "X".repeat(MAX_SYMBOL_LEN),
+                feed_id: [i; 32],
+            })
+            .collect();
+        let registry = SymbolRegistry { entries, bump: 255 };
+        assert_eq!(registry.try_to_vec().unwrap().len(), SymbolRegistry::INIT_SPACE);
+    }
+
+    #[test]
+    fn resolves_only_registered_symbols() {
+        let eth = get_feed_id_from_hex(DEFAULT_FEED_ID_HEX).unwrap();
+        let registry = SymbolRegistry {
+            entries: vec![SymbolEntry {
+                symbol: "ETH/USD".to_string(),
+                feed_id: eth,
+            }],
+            bump: 255,
+        };
+        assert_eq!(registry.feed_id("ETH/USD").unwrap(), eth);
+        let unknown: Error = ErrorCode::UnknownSymbol.into();
+        assert_eq!(registry.feed_id("BTC/USD").unwrap_err(), unknown);
+        // no normalization: a near miss is still unknown
+        assert_eq!(registry.feed_id("eth/usd").unwrap_err(), unknown);
+        assert_eq!(registry.feed_id("").unwrap_err(), unknown);
+    }
+
+    #[test]
+    fn scratch_size_matches_serialized_size() {
+        let scratch = PriceScratch {
+            authority: Pubkey::new_from_array([1; 32]),
+            price: ValidatedPrice {
+                feed_id: [2; 32],
+                price: i64::MIN,
+                conf: u64::MAX,
+                exponent: -8,
+                publish_time: i64::MAX,
+            },
+        };
+        assert_eq!(scratch.try_to_vec().unwrap().len(), PriceScratch::SIZE);
+    }
+
+    #[test]
+    fn twap_space_matches_serialized_size() {
+        let mut twap = twap_state(MAX_TWAP_CAPACITY);
+        for i in 0..i64::from(MAX_TWAP_CAPACITY) {
+            twap.push(sample(i, i));
+        }
+        assert_eq!(8 + twap.try_to_vec().unwrap().len(), TwapState::space(MAX_TWAP_CAPACITY));
+    }
+}
@@ -0,0 +1,608 @@
+use core::cmp::Ordering;
+
+use anchor_lang::prelude::*;
+
+use crate::ErrorCode;
+
+/// Largest power of ten that fits in an i128 (10^38 < i128::MAX < 10^39).
+const MAX_POW10: u32 = 38;
+
+/// Most fixed-point decimals any helper here will produce (18 covers 6-decimal USDC up to
+/// 18-decimal wei-style amounts).
+pub const MAX_TARGET_DECIMALS: u32 = 18;
+
+/// How to round when a decimal shift drops digits.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundingMode {
+    TowardZero,
+    /// Toward negative infinity, e.g. for collateral
+    Floor,
+    /// Toward positive infinity, e.g. for debt
+    Ceil,
+    /// To nearest, ties to the even neighbour (banker's rounding)
+    HalfEven,
+}
+
+/// How `aggregate` combines several prices into one.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AggregateMode {
+    /// Lowest price, e.g. to value collateral conservatively
+    Min,
+    /// Highest price, e.g. to value debt conservatively
+    Max,
+    /// Middle price; the mean of the middle two for an even count, rounded toward zero
+    Median,
+    /// Arithmetic mean, rounded toward zero
+    Mean,
+}
+
+/// Convert a raw Pyth `price * 10^exponent` into a fixed-point integer with `target_decimals`.
+///
+/// e.g. `scale_price(182345, -2, 8, _)` = 1823.45 at 8 decimals = `182_345_000_000`.
+/// Precision lost when shifting right is rounded per `mode`; overflow returns `ScaleOverflow`.
+pub fn scale_price(price: i64, exponent: i32, target_decimals: u32, mode: RoundingMode) -> Result<i128> {
+    check_decimals(target_decimals)?;
+    // price * 10^exponent * 10^target_decimals = price * 10^shift
+    let shift = i64::from(exponent) + i64::from(target_decimals);
+    shift_decimals(i128::from(price), shift, mode)
+}
+
+/// Value of `amount` base units of a token with `token_decimals`, at a price of
+/// `price * 10^exponent` per whole token, as a fixed-point integer with `target_decimals`.
+///
+/// The caller must reject non-positive prices first; lost precision is rounded per `mode`.
+pub fn usd_value(
+    amount: u64,
+    token_decimals: u8,
+    price: i64,
+    exponent: i32,
+    target_decimals: u32,
+    mode: RoundingMode,
+) -> Result<i128> {
+    check_decimals(target_decimals)?;
+    // u64 * i64 always fits in i128, so only the decimal shift can overflow
+    let raw = i128::from(amount) * i128::from(price);
+    let shift = i64::from(exponent) + i64::from(target_decimals) - i64::from(token_decimals);
+    shift_decimals(raw, shift, mode)
+}
+
+/// Ratio `(base * 10^base_exponent) / (quote * 10^quote_exponent)` as a fixed-point integer with
+/// `target_decimals`, rounded toward zero. Exponents are combined before dividing so no precision
+/// is lost to an intermediate scale.
+///
+/// A combined shift past `10^38` either way has no i128 representation and returns `ScaleOverflow`
+/// instead of collapsing the ratio to 0.
+pub fn cross_ratio(
+    base: i64,
+    base_exponent: i32,
+    quote: i64,
+    quote_exponent: i32,
+    target_decimals: u32,
+) -> Result<i128> {
+    check_decimals(target_decimals)?;
+    require!(quote != 0, ErrorCode::ZeroPrice);
+    let shift = i64::from(base_exponent) - i64::from(quote_exponent) + i64::from(target_decimals);
+    require!(shift.unsigned_abs() <= u64::from(MAX_POW10), ErrorCode::ScaleOverflow);
+    // trunc(trunc(a / 10^k) / q) == trunc(a / (10^k * q)), so shifting first is exact
+    Ok(shift_decimals(i128::from(base), shift, RoundingMode::TowardZero)? / i128::from(quote))
+}
+
+/// Time-weighted average of chronological `(price, publish_time)` samples, rounded toward zero.
+///
+/// Each price is weighted by how long it stood before the next sample, so the last sample only
+/// closes the window.
+pub fn time_weighted_average(samples: &[(i64, i64)]) -> Result<i64> {
+    require!(samples.len() >= 2, ErrorCode::NotEnoughSamples);
+    let span = i128::from(samples[samples.len() - 1].1) - i128::from(samples[0].1);
+    require!(span > 0, ErrorCode::NotEnoughSamples);
+
+    let mut weighted: i128 = 0;
+    for pair in samples.windows(2) {
+        let dt = i128::from(pair[1].1) - i128::from(pair[0].1);
+        weighted = i128::from(pair[0].0)
+            .checked_mul(dt)
+            .and_then(|term| weighted.checked_add(term))
+            .ok_or(error!(ErrorCode::ScaleOverflow))?;
+    }
+
+    // A weighted mean lies between the min and max price, so it fits in i64
+    Ok((weighted / span) as i64)
+}
+
+/// `sum(value * weight) / sum(weight)` over `(value, weight)` pairs, rounded toward zero.
+pub fn weighted_average(items: &[(i128, u64)]) -> Result<i128> {
+    let total_weight: u128 = items.iter().map(|&(_, w)| u128::from(w)).sum();
+    require!(total_weight > 0, ErrorCode::ZeroWeight);
+
+    let mut weighted: i128 = 0;
+    for &(value, weight) in items {
+        weighted = value
+            .checked_mul(i128::from(weight))
+            .and_then(|term| weighted.checked_add(term))
+            .ok_or(error!(ErrorCode::MathOverflow))?;
+    }
+    // Reaching i128::MAX would take 2^63 maximal u64 weights, so the cast can't wrap
+    Ok(weighted / total_weight as i128)
+}
+
+/// Combine prices that share decimals per `mode`. Sorts `values` in place for the median.
+pub fn aggregate(values: &mut [i128], mode: AggregateMode) -> Result<i128> {
+    require!(!values.is_empty(), ErrorCode::NoFeeds);
+    let n = values.len();
+
+    match mode {
+        AggregateMode::Min => Ok(values.iter().copied().min().unwrap_or_default()),
+        AggregateMode::Max => Ok(values.iter().copied().max().unwrap_or_default()),
+        AggregateMode::Median => {
+            // At most MAX_FEEDS entries, so a plain sort is cheap enough on-chain
+            values.sort_unstable();
+            if n % 2 == 1 {
+                return Ok(values[n / 2]);
+            }
+            let sum = values[n / 2 - 1]
+                .checked_add(values[n / 2])
+                .ok_or(error!(ErrorCode::MathOverflow))?;
+            Ok(sum / 2)
+        }
+        AggregateMode::Mean => {
+            let sum = values
+                .iter()
+                .try_fold(0i128, |acc, &v| acc.checked_add(v))
+                .ok_or(error!(ErrorCode::MathOverflow))?;
+            Ok(sum / n as i128)
+        }
+    }
+}
+
+/// `|price - reference| / |reference|` in basis points, rounded down. Both prices must share an
+/// exponent.
+pub fn deviation_bps(price: i64, reference: i64) -> Result<u128> {
+    require!(reference != 0, ErrorCode::ZeroPrice);
+    // |i64 - i64| fits in u128, and so does that times 10_000
+    let diff = (i128::from(price) - i128::from(reference)).unsigned_abs();
+    Ok(diff * 10_000 / u128::from(reference.unsigned_abs()))
+}
+
+/// `value * 10^shift`, rounded per `mode` when `shift` is negative.
+fn shift_decimals(value: i128, shift: i64, mode: RoundingMode) -> Result<i128> {
+    if shift >= 0 {
+        let factor = pow10(u32::try_from(shift).map_err(|_| error!(ErrorCode::ScaleOverflow))?)?;
+        return value.checked_mul(factor).ok_or(error!(ErrorCode::ScaleOverflow));
+    }
+
+    let shift = shift.unsigned_abs();
+    // The remainder's size against half the divisor, for HalfEven
+    let (quotient, remainder, vs_half) = if shift > u64::from(MAX_POW10) {
+        // |value| < 1.7 * 10^38, so a divisor beyond 10^38 leaves all of it as a remainder that
+        // is under half the divisor
+        (0, value, Ordering::Less)
+    } else {
+        let divisor = pow10(shift as u32)?;
+        // i128 division truncates toward zero, and the remainder takes the sign of value
+        let remainder = value % divisor;
+        // 2 * |remainder| < 2 * 10^38 fits in u128
+        let vs_half = (2 * remainder.unsigned_abs()).cmp(&divisor.unsigned_abs());
+        (value / divisor, remainder, vs_half)
+    };
+    if remainder == 0 {
+        return Ok(quotient);
+    }
+
+    let away_from_zero = match mode {
+        RoundingMode::TowardZero => false,
+        RoundingMode::Floor => remainder < 0,
+        RoundingMode::Ceil => remainder > 0,
+        RoundingMode::HalfEven => match vs_half {
+            Ordering::Less => false,
+            Ordering::Equal => quotient % 2 != 0,
+            Ordering::Greater => true,
+        },
+    };
+    // |quotient| < i128::MAX, so one step away from zero can't overflow
+    Ok(if away_from_zero { quotient + remainder.signum() } else { quotient })
+}
+
+fn check_decimals(target_decimals: u32) -> Result<()> {
+    require!(target_decimals <= MAX_TARGET_DECIMALS, ErrorCode::InvalidDecimals);
+    Ok(())
+}
+
+fn pow10(exp: u32) -> Result<i128> {
+    10i128.checked_pow(exp).ok_or(error!(ErrorCode::ScaleOverflow))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RoundingMode::*;
+    use super::*;
+
+    #[test]
+    fn scales_negative_exponent() {
+        assert_eq!(scale_price(182_345, -2, 8, TowardZero).unwrap(), 182_345_000_000);
+        assert_eq!(scale_price(182_345, -8, 8, TowardZero).unwrap(), 182_345);
+    }
+
+    #[test]
+    fn exponent_zero() {
+        assert_eq!(scale_price(7, 0, 0, TowardZero).unwrap(), 7);
+        assert_eq!(scale_price(7, 0, 8, TowardZero).unwrap(), 700_000_000);
+    }
+
+    #[test]
+    fn positive_exponent() {
+        assert_eq!(scale_price(3, 2, 2, TowardZero).unwrap(), 30_000);
+    }
+
+    #[test]
+    fn rounds_toward_zero() {
+        // 1.23456789 -> 1.2345 at 4 decimals, -1.23456789 -> -1.2345
+        assert_eq!(scale_price(123_456_789, -8, 4, TowardZero).unwrap(), 12_345);
+        assert_eq!(scale_price(-123_456_789, -8, 4, TowardZero).unwrap(), -12_345);
+        assert_eq!(scale_price(9, -1, 0, TowardZero).unwrap(), 0);
+        assert_eq!(scale_price(-9, -1, 0, TowardZero).unwrap(), 0);
+    }
+
+    #[test]
+    fn very_negative_exponent_truncates_to_zero() {
+        assert_eq!(scale_price(i64::MAX, -60, 8, TowardZero).unwrap(), 0);
+        assert_eq!(scale_price(i64::MIN, i32::MIN, 8, TowardZero).unwrap(), 0);
+        assert_eq!(scale_price(i64::MAX, -46, 8, TowardZero).unwrap(), 0);
+    }
+
+    #[test]
+    fn near_i64_max() {
+        assert_eq!(
+            scale_price(i64::MAX, -8, 8, TowardZero).unwrap(),
+            i128::from(i64::MAX)
+        );
+        assert_eq!(
+            scale_price(i64::MAX, 0, 18, TowardZero).unwrap(),
+            i128::from(i64::MAX) * 10i128.pow(18)
+        );
+        assert_eq!(
+            scale_price(i64::MIN, 0, 18, TowardZero).unwrap(),
+            i128::from(i64::MIN) * 10i128.pow(18)
+        );
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        let overflow: Error = ErrorCode::ScaleOverflow.into();
+        // 9.2e18 * 10^20 > i128::MAX
+        assert_eq!(scale_price(i64::MAX, 12, 8, TowardZero).unwrap_err(), overflow);
+        // 10^39 does not fit at all
+        assert_eq!(scale_price(1, 31, 8, TowardZero).unwrap_err(), overflow);
+        assert_eq!(scale_price(1, i32::MAX, 8, TowardZero).unwrap_err(), overflow);
+    }
+
+    #[test]
+    fn usd_value_of_whole_and_fractional_tokens() {
+        // 2.5 ETH (18 decimals) at $1823.45 = $4558.625
+        let amount = 2_500_000_000_000_000_000;
+        assert_eq!(usd_value(amount, 18, 182_345, -2, 8, TowardZero).unwrap(), 455_862_500_000);
+        // 1 USDC (6 decimals) at $0.99990000
+        assert_eq!(usd_value(1_000_000, 6, 99_990_000, -8, 8, TowardZero).unwrap(), 99_990_000);
+        // dust rounds toward zero
+        assert_eq!(usd_value(1, 18, 182_345, -2, 8, TowardZero).unwrap(), 0);
+    }
+
+    #[test]
+    fn usd_value_at_extremes() {
+        assert_eq!(
+            usd_value(u64::MAX, 0, i64::MAX, 0, 0, TowardZero).unwrap(),
+            i128::from(u64::MAX) * i128::from(i64::MAX)
+        );
+        assert_eq!(
+            usd_value(u64::MAX, 0, i64::MAX, 0, 8, TowardZero).unwrap_err(),
+            ErrorCode::ScaleOverflow.into()
+        );
+        assert_eq!(usd_value(0, 9, i64::MAX, 0, 8, TowardZero).unwrap(), 0);
+    }
+
+    #[test]
+    fn cross_ratio_with_matching_exponents() {
+        // ETH/USD 2000.00 over BTC/USD 40000.00 = 0.05 ETH/BTC
+        assert_eq!(cross_ratio(200_000, -2, 4_000_000, -2, 8).unwrap(), 5_000_000);
+        // and the inverse, 20 BTC/ETH
+        assert_eq!(cross_ratio(4_000_000, -2, 200_000, -2, 8).unwrap(), 2_000_000_000);
+    }
+
+    #[test]
+    fn cross_ratio_with_different_exponents() {
+        // 2000 (exponent -2) over 40000 (exponent -8) = 0.05
+        assert_eq!(
+            cross_ratio(200_000, -2, 4_000_000_000_000, -8, 8).unwrap(),
+            5_000_000
+        );
+        // 1/3 rounds toward zero, sign follows the operands
+        assert_eq!(cross_ratio(1, 0, 3, 0, 4).unwrap(), 3_333);
+        assert_eq!(cross_ratio(-1, 0, 3, 0, 4).unwrap(), -3_333);
+    }
+
+    #[test]
+    fn twap_weights_by_duration() {
+        // 100 for 10s, then 200 for 30s
+        assert_eq!(time_weighted_average(&[(100, 0), (200, 10), (999, 40)]).unwrap(), 175);
+        // equal spacing is a plain mean of all but the last sample
+        assert_eq!(time_weighted_average(&[(10, 5), (20, 6), (30, 7)]).unwrap(), 15);
+        assert_eq!(time_weighted_average(&[(-10, 0), (0, 3)]).unwrap(), -10);
+    }
+
+    #[test]
+    fn twap_needs_a_window() {
+        let not_enough: Error = ErrorCode::NotEnoughSamples.into();
+        assert_eq!(time_weighted_average(&[]).unwrap_err(), not_enough);
+        assert_eq!(time_weighted_average(&[(1, 1)]).unwrap_err(), not_enough);
+        assert_eq!(time_weighted_average(&[(1, 1), (2, 1)]).unwrap_err(), not_enough);
+    }
+
+    #[test]
+    fn twap_at_extremes() {
+        assert_eq!(
+            time_weighted_average(&[(i64::MAX, i64::MIN), (0, i64::MAX)]).unwrap(),
+            i64::MAX
+        );
+    }
+
+    #[test]
+    fn deviation_in_either_direction() {
+        assert_eq!(deviation_bps(100, 100).unwrap(), 0);
+        assert_eq!(deviation_bps(105, 100).unwrap(), 500);
+        assert_eq!(deviation_bps(95, 100).unwrap(), 500);
+        // rounds down, and negative references use their magnitude
+        assert_eq!(deviation_bps(10_001, 10_000).unwrap(), 1);
+        assert_eq!(deviation_bps(1_000_099, 1_000_000).unwrap(), 0);
+        assert_eq!(deviation_bps(-110, -100).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn deviation_at_extremes() {
+        assert_eq!(
+            deviation_bps(i64::MAX, i64::MIN).unwrap(),
+            (i128::from(i64::MAX) - i128::from(i64::MIN)).unsigned_abs() * 10_000
+                / u128::from(i64::MIN.unsigned_abs())
+        );
+        assert_eq!(deviation_bps(i64::MAX, 1).unwrap(), (i64::MAX as u128 - 1) * 10_000);
+        assert_eq!(deviation_bps(1, 0).unwrap_err(), ErrorCode::ZeroPrice.into());
+    }
+
+    #[test]
+    fn same_inputs_at_6_8_and_18_decimals() {
+        // $1823.45
+        assert_eq!(scale_price(182_345, -2, 6, TowardZero).unwrap(), 1_823_450_000);
+        assert_eq!(scale_price(182_345, -2, 8, TowardZero).unwrap(), 182_345_000_000);
+        assert_eq!(scale_price(182_345, -2, 18, TowardZero).unwrap(), 1_823_450_000_000_000_000_000);
+
+        // 2.5 ETH at $1823.45 = $4558.625
+        let amount = 2_500_000_000_000_000_000;
+        assert_eq!(usd_value(amount, 18, 182_345, -2, 6, TowardZero).unwrap(), 4_558_625_000);
+        assert_eq!(usd_value(amount, 18, 182_345, -2, 8, TowardZero).unwrap(), 455_862_500_000);
+        assert_eq!(
+            usd_value(amount, 18, 182_345, -2, 18, TowardZero).unwrap(),
+            4_558_625_000_000_000_000_000
+        );
+
+        // 1/3 keeps as many digits as asked for, each rounded toward zero
+        assert_eq!(cross_ratio(1, 0, 3, 0, 6).unwrap(), 333_333);
+        assert_eq!(cross_ratio(1, 0, 3, 0, 8).unwrap(), 33_333_333);
+        assert_eq!(cross_ratio(1, 0, 3, 0, 18).unwrap(), 333_333_333_333_333_333);
+    }
+
+    #[test]
+    fn rejects_more_than_18_decimals() {
+        let invalid: Error = ErrorCode::InvalidDecimals.into();
+        assert_eq!(scale_price(1, 0, 19, TowardZero).unwrap_err(), invalid);
+        assert_eq!(usd_value(1, 0, 1, 0, 19, TowardZero).unwrap_err(), invalid);
+        assert_eq!(cross_ratio(1, 0, 1, 0, u32::MAX).unwrap_err(), invalid);
+    }
+
+    #[test]
+    fn weighted_average_of_a_basket() {
+        // 60% at 100, 40% at 200
+        assert_eq!(weighted_average(&[(100, 3), (200, 2)]).unwrap(), 140);
+        // zero weights drop out, and the mean rounds toward zero
+        assert_eq!(weighted_average(&[(7, 0), (10, 1), (11, 1)]).unwrap(), 10);
+        assert_eq!(weighted_average(&[(-10, 1), (-11, 1)]).unwrap(), -10);
+        assert_eq!(
+            weighted_average(&[(i128::from(i64::MAX), u64::MAX)]).unwrap(),
+            i128::from(i64::MAX)
+        );
+    }
+
+    #[test]
+    fn weighted_average_rejects_zero_weight_and_overflow() {
+        let zero: Error = ErrorCode::ZeroWeight.into();
+        assert_eq!(weighted_average(&[]).unwrap_err(), zero);
+        assert_eq!(weighted_average(&[(1, 0), (2, 0)]).unwrap_err(), zero);
+        assert_eq!(
+            weighted_average(&[(i128::MAX / 2, 2), (i128::MAX / 2, 2)]).unwrap_err(),
+            ErrorCode::MathOverflow.into()
+        );
+    }
+
+    #[test]
+    fn aggregates_per_mode() {
+        use super::AggregateMode::*;
+        let of = |values: &[i128], mode| aggregate(&mut values.to_vec(), mode).unwrap();
+        let prices = [300, -5, 100, 200];
+        assert_eq!(of(&prices, Min), -5);
+        assert_eq!(of(&prices, Max), 300);
+        // even count: mean of 100 and 200
+        assert_eq!(of(&prices, Median), 150);
+        assert_eq!(of(&prices[..3], Median), 100);
+        // 595 / 4 = 148.75, rounded toward zero
+        assert_eq!(of(&prices, Mean), 148);
+        assert_eq!(of(&[-3, -4], Median), -3);
+        assert_eq!(of(&[-3, -4], Mean), -3);
+        assert_eq!(of(&[7], Median), 7);
+    }
+
+    #[test]
+    fn aggregate_rejects_no_feeds_and_overflow() {
+        use super::AggregateMode::*;
+        for mode in [Min, Max, Median, Mean] {
+            assert_eq!(aggregate(&mut [], mode).unwrap_err(), ErrorCode::NoFeeds.into());
+        }
+        let overflow: Error = ErrorCode::MathOverflow.into();
+        assert_eq!(aggregate(&mut [i128::MAX, i128::MAX], Median).unwrap_err(), overflow);
+        assert_eq!(aggregate(&mut [i128::MAX, 1], Mean).unwrap_err(), overflow);
+        assert_eq!(aggregate(&mut [i128::MAX, 1], Max).unwrap(), i128::MAX);
+    }
+
+    #[test]
+    fn rounds_per_mode() {
+        // 1.25, 1.35, -1.25 and 1.2 at one decimal (exponent -2 -> 1 decimal)
+        let at = |price, mode| scale_price(price, -2, 1, mode).unwrap();
+        assert_eq!(
+            [at(125, TowardZero), at(125, Floor), at(125, Ceil), at(125, HalfEven)],
+            [12, 12, 13, 12]
+        );
+        assert_eq!(
+            [at(135, TowardZero), at(135, Floor), at(135, Ceil), at(135, HalfEven)],
+            [13, 13, 14, 14]
+        );
+        assert_eq!(
+            [at(-125, TowardZero), at(-125, Floor), at(-125, Ceil), at(-125, HalfEven)],
+            [-12, -13, -12, -12]
+        );
+        assert_eq!([at(126, HalfEven), at(-126, HalfEven), at(124, HalfEven)], [13, -13, 12]);
+        assert_eq!(
+            [at(120, TowardZero), at(120, Floor), at(120, Ceil), at(120, HalfEven)],
+            [12, 12, 12, 12]
+        );
+    }
+
+    #[test]
+    fn rounds_values_below_one_unit() {
+        // 0.5 and -0.5 units, and dust far below the last decimal
+        assert_eq!(scale_price(5, -1, 0, HalfEven).unwrap(), 0);
+        assert_eq!(scale_price(-5, -1, 0, Floor).unwrap(), -1);
+        assert_eq!(scale_price(1, -60, 8, Ceil).unwrap(), 1);
+        assert_eq!(scale_price(-1, -60, 8, Floor).unwrap(), -1);
+        assert_eq!(scale_price(i64::MAX, -60, 8, HalfEven).unwrap(), 0);
+        assert_eq!(scale_price(i64::MIN, i32::MIN, 8, Ceil).unwrap(), 0);
+    }
+
+    #[test]
+    fn usd_value_per_mode() {
+        // 1 base unit of an 18-decimal token at $1823.45 is $0.00000000000000182345
+        let dust = |mode| usd_value(1, 18, 182_345, -2, 8, mode).unwrap();
+        assert_eq!([dust(TowardZero), dust(Floor), dust(Ceil), dust(HalfEven)], [0, 0, 1, 0]);
+        // $4558.625 at 2 decimals: collateral floors, debt ceils, half-even ties to 4558.62
+        let value = |mode| usd_value(2_500_000_000_000_000_000, 18, 182_345, -2, 2, mode).unwrap();
+        assert_eq!([value(Floor), value(Ceil), value(HalfEven)], [455_862, 455_863, 455_862]);
+    }
+
+    #[test]
+    fn cross_ratio_of_cent_and_nano_exponents() {
+        // 2000.00 (exponent -2) over 4000.000000000 (exponent -9) = 0.5, and back = 2
+        assert_eq!(cross_ratio(200_000, -2, 4_000_000_000_000, -9, 8).unwrap(), 50_000_000);
+        assert_eq!(cross_ratio(4_000_000_000_000, -9, 200_000, -2, 8).unwrap(), 200_000_000);
+        // a shift of +25 still fits with room to spare
+        assert_eq!(
+            cross_ratio(200_000, -2, 4_000_000_000_000, -9, 18).unwrap(),
+            500_000_000_000_000_000
+        );
+        // sub-cent base against a nano quote: 0.01 / 4000 = 0.0000025
+        assert_eq!(cross_ratio(1, -2, 4_000_000_000_000, -9, 8).unwrap(), 250);
+    }
+
+    #[test]
+    fn cross_ratio_shift_out_of_range() {
+        let overflow: Error = ErrorCode::ScaleOverflow.into();
+        // 9.2e18 * 10^25 does not fit in i128
+        assert_eq!(cross_ratio(i64::MAX, -2, 1, -9, 18).unwrap_err(), overflow);
+        // shifts past 10^38 either way are rejected rather than truncated to 0
+        assert_eq!(cross_ratio(i64::MAX, -2, 1, 37, 0).unwrap_err(), overflow);
+        assert_eq!(cross_ratio(i64::MAX, -9, 1, 39, 8).unwrap_err(), overflow);
+        assert_eq!(cross_ratio(1, i32::MIN, 1, i32::MAX, 18).unwrap_err(), overflow);
+        // 10^-38 is the last representable step down
+        assert_eq!(cross_ratio(i64::MAX, -9, 1, 37, 8).unwrap(), 0);
+    }
+
+    #[test]
+    fn cross_ratio_rejects_zero_quote() {
+        assert_eq!(
+            cross_ratio(1, 0, 0, 0, 8).unwrap_err(),
+            ErrorCode::ZeroPrice.into()
+        );
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use proptest::prelude::*;
+
+    use super::RoundingMode::*;
+    use super::*;
+
+    fn any_mode() -> impl Strategy<Value = RoundingMode> {
+        prop_oneof![Just(TowardZero), Just(Floor), Just(Ceil), Just(HalfEven)]
+    }
+
+    proptest! {
+        #[test]
+        fn scaling_never_panics(
+            price in any::<i64>(),
+            exponent in any::<i32>(),
+            target_decimals in any::<u32>(),
+            mode in any_mode(),
+        ) {
+            let _ = scale_price(price, exponent, target_decimals, mode);
+        }
+
+        #[test]
+        fn toward_zero_matches_exact_arithmetic(
+            price in any::<i64>(),
+            exponent in -40i32..=20,
+            target_decimals in 0..=MAX_TARGET_DECIMALS,
+        ) {
+            let shift = exponent + target_decimals as i32;
+            let exact = if shift >= 0 {
+                10i128.checked_pow(shift as u32).and_then(|f| i128::from(price).checked_mul(f))
+            } else {
+                // past 10^38 every i64 truncates to 0
+                Some(10i128.checked_pow(shift.unsigned_abs()).map_or(0, |d| i128::from(price) / d))
+            };
+            match exact {
+                Some(v) => prop_assert_eq!(scale_price(price, exponent, target_decimals, TowardZero).unwrap(), v),
+                None => prop_assert!(scale_price(price, exponent, target_decimals, TowardZero).is_err()),
+            }
+        }
+
+        #[test]
+        fn scaling_is_monotonic(
+            a in any::<i64>(),
+            b in any::<i64>(),
+            exponent in -40i32..=20,
+            target_decimals in 0..=MAX_TARGET_DECIMALS,
+            mode in any_mode(),
+        ) {
+            let (lo, hi) = (a.min(b), a.max(b));
+            if let (Ok(lo), Ok(hi)) = (
+                scale_price(lo, exponent, target_decimals, mode),
+                scale_price(hi, exponent, target_decimals, mode),
+            ) {
+                prop_assert!(lo <= hi);
+            }
+        }
+
+        #[test]
+        fn rounding_modes_bracket_the_exact_value(
+            price in any::<i64>(),
+            exponent in -40i32..=0,
+            target_decimals in 0..=MAX_TARGET_DECIMALS,
+        ) {
+            // Right shifts only, which always fit
+            let scale = |mode| scale_price(price, exponent, target_decimals, mode).unwrap();
+            let (floor, ceil) = (scale(Floor), scale(Ceil));
+            prop_assert!(ceil - floor <= 1);
+            for mode in [TowardZero, HalfEven] {
+                prop_assert!((floor..=ceil).contains(&scale(mode)));
+            }
+            // toward zero keeps the sign, or lands on 0
+            let truncated = scale(TowardZero);
+            prop_assert!(truncated == 0 || truncated.signum() == i128::from(price.signum()));
+        }
+    }
+}
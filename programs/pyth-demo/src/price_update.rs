@@ -0,0 +1,79 @@
+use anchor_lang::prelude::*;
+use pyth_solana_receiver_sdk::price_update::{FeedId, Price, PriceUpdateV2, VerificationLevel};
+
+use crate::{decode_price_update, ErrorCode, PYTH_RECEIVER_PROGRAM_ID};
+
+/// Any receiver price update account version, behind one set of accessors.
+///
+/// Only `PriceUpdateV2` exists today. A new version gets a variant and a discriminator arm in
+/// `decode`; code reading prices through this type does not change.
+#[derive(Clone)]
+pub enum AnyPriceUpdate {
+    V2(PriceUpdateV2),
+}
+
+impl AnyPriceUpdate {
+    /// Decode account data of any supported version, chosen by its discriminator.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.starts_with(PriceUpdateV2::DISCRIMINATOR) {
+            return decode_price_update(data).map(AnyPriceUpdate::V2);
+        }
+        err!(ErrorCode::NotAPriceUpdate)
+    }
+
+    /// Decode an account the receiver program owns.
+    pub fn try_from_account(info: &AccountInfo) -> Result<Self> {
+        require_keys_eq!(*info.owner, PYTH_RECEIVER_PROGRAM_ID, ErrorCode::InvalidPriceAccountOwner);
+        Self::decode(&info.try_borrow_data()?)
+    }
+
+    pub fn verification_level(&self) -> VerificationLevel {
+        match self {
+            AnyPriceUpdate::V2(u) => u.verification_level,
+        }
+    }
+
+    /// Feed the update carries a price for.
+    pub fn feed_id(&self) -> FeedId {
+        match self {
+            AnyPriceUpdate::V2(u) => u.price_message.feed_id,
+        }
+    }
+
+    /// Spot price for `feed_id` without freshness or verification checks.
+    pub fn get_price_unchecked(&self, feed_id: &FeedId) -> Result<Price> {
+        match self {
+            AnyPriceUpdate::V2(u) => Ok(u.get_price_unchecked(feed_id)?),
+        }
+    }
+
+    /// EMA price and conf for `feed_id`, published alongside (and timed as) the spot price.
+    pub fn get_ema_price_unchecked(&self, feed_id: &FeedId) -> Result<Price> {
+        match self {
+            AnyPriceUpdate::V2(u) => Ok(Price {
+                price: u.price_message.ema_price,
+                conf: u.price_message.ema_conf,
+                ..u.get_price_unchecked(feed_id)?
+            }),
+        }
+    }
+
+    /// Fully verified spot price for `feed_id` no older than `max_age` seconds.
+    pub fn get_price_no_older_than(&self, clock: &Clock, max_age: u64, feed_id: &FeedId) -> Result<Price> {
+        match self {
+            AnyPriceUpdate::V2(u) => Ok(u.get_price_no_older_than(clock, max_age, feed_id)?),
+        }
+    }
+}
+
+impl From<&PriceUpdateV2> for AnyPriceUpdate {
+    fn from(u: &PriceUpdateV2) -> Self {
+        AnyPriceUpdate::V2(u.clone())
+    }
+}
+
+impl From<&Account<'_, PriceUpdateV2>> for AnyPriceUpdate {
+    fn from(u: &Account<'_, PriceUpdateV2>) -> Self {
+        AnyPriceUpdate::from(&**u)
+    }
+}
@@ -0,0 +1,40 @@
+//! Fabricated receiver accounts for tests, so validation paths can be exercised without devnet.
+//! Compiled for this crate's unit tests, and for other crates with the `test-utils` feature.
+
+use anchor_lang::prelude::*;
+use pyth_solana_receiver_sdk::price_update::{PriceFeedMessage, PriceUpdateV2, VerificationLevel};
+
+/// Account data of a fully verified `PriceUpdateV2` for `feed_id`, as the receiver would store it.
+///
+/// The program reads only the chosen fields and the verification level. The rest are stubbed:
+/// `write_authority` is an arbitrary key, `prev_publish_time` is one second before `publish_time`,
+/// the EMA price and conf repeat the spot ones, and `posted_slot` is 0. The account must still be
+/// owned by the receiver program (`pyth_solana_receiver_sdk::ID`) wherever an owner is checked.
+pub fn make_price_update(
+    feed_id: [u8; 32],
+    price: i64,
+    conf: u64,
+    exponent: i32,
+    publish_time: i64,
+) -> Vec<u8> {
+    let update = PriceUpdateV2 {
+        write_authority: Pubkey::new_from_array([9; 32]),
+        verification_level: VerificationLevel::Full,
+        price_message: PriceFeedMessage {
+            feed_id,
+            price,
+            conf,
+            exponent,
+            publish_time,
+            prev_publish_time: publish_time.saturating_sub(1),
+            ema_price: price,
+            ema_conf: conf,
+        },
+        posted_slot: 0,
+    };
+    let mut data = Vec::new();
+    update.try_serialize(&mut data).expect("a Vec grows to fit");
+    // Receiver accounts are allocated at the full size whatever the verification level takes
+    data.resize(PriceUpdateV2::LEN, 0);
+    data
+}
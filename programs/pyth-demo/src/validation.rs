@@ -0,0 +1,329 @@
+use anchor_lang::prelude::*;
+
+use crate::ErrorCode;
+
+/// Tolerated publish_time lead over the cluster clock, in seconds.
+pub const MAX_FUTURE_SKEW_SECS: i64 = 10;
+
+/// Target slot time the slot-based freshness window assumes; real slots drift above and below it.
+pub const MS_PER_SLOT: u64 = 400;
+
+/// Seconds spanned by `max_age_slots` at `MS_PER_SLOT`, rounded up so a short window never becomes 0.
+///
+/// Only an approximation: PriceUpdateV2 has no observation slot (its `posted_slot` is when the update
+/// landed on Solana, not when Pyth priced it), so slot windows are enforced against `publish_time`.
+pub fn slots_to_secs(max_age_slots: u64) -> u64 {
+    (u128::from(max_age_slots) * u128::from(MS_PER_SLOT)).div_ceil(1_000) as u64
+}
+
+/// Why an observation failed `validate_observation`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationError {
+    /// `max_age` was zero
+    InvalidArgs,
+    /// Older than `max_age` seconds
+    PriceTooOld,
+    /// More than `MAX_FUTURE_SKEW_SECS` ahead of `now`
+    FuturePrice,
+    ZeroPrice,
+    /// conf/|price| above a nonzero `max_conf_bps`
+    WideConfidence,
+    /// The confidence ratio did not fit in u128
+    MathOverflow,
+}
+
+/// Freshness and confidence rules for one Pyth observation, independent of any account or clock.
+///
+/// An observation is fresh when `now - max_age <= publish_time <= now + MAX_FUTURE_SKEW_SECS`, and
+/// its confidence is acceptable when `conf * 10_000 / |price| <= max_conf_bps`. A `max_conf_bps` of 0
+/// skips the confidence check rather than demanding zero confidence, which no real feed reports.
+/// Negative prices are judged on their magnitude; rejecting them is up to the caller.
+pub fn validate_observation(
+    price: i64,
+    conf: u64,
+    exponent: i32,
+    publish_time: i64,
+    now: i64,
+    max_age: u64,
+    max_conf_bps: u64,
+) -> core::result::Result<(), ValidationError> {
+    // conf and price share the exponent, so no rule here depends on it
+    let _ = exponent;
+
+    if max_age == 0 {
+        return Err(ValidationError::InvalidArgs);
+    }
+
+    // i128 so extreme timestamps and ages can't overflow
+    let publish_time = i128::from(publish_time);
+    let now = i128::from(now);
+    if publish_time + i128::from(max_age) < now {
+        return Err(ValidationError::PriceTooOld);
+    }
+    if publish_time > now + i128::from(MAX_FUTURE_SKEW_SECS) {
+        return Err(ValidationError::FuturePrice);
+    }
+
+    if price == 0 {
+        return Err(ValidationError::ZeroPrice);
+    }
+    if max_conf_bps == 0 {
+        return Ok(());
+    }
+    // |price| > 0 after the zero check, so None can only be an overflow
+    let ratio = conf_ratio_bps(conf, price).ok_or(ValidationError::MathOverflow)?;
+    if ratio > u128::from(max_conf_bps) {
+        return Err(ValidationError::WideConfidence);
+    }
+
+    Ok(())
+}
+
+/// Seconds from `publish_time` to `now`, in i128 so no pair of timestamps can wrap.
+///
+/// A lead of up to `MAX_FUTURE_SKEW_SECS` over the clock counts as age 0, the same tolerance
+/// `validate_observation` gives; anything further ahead is `FuturePrice`, never a wrapped age.
+pub fn observation_age(publish_time: i64, now: i64) -> core::result::Result<u64, ValidationError> {
+    let age = i128::from(now) - i128::from(publish_time);
+    if age < -i128::from(MAX_FUTURE_SKEW_SECS) {
+        return Err(ValidationError::FuturePrice);
+    }
+    // i64 - i64 spans at most 2^64 - 1, so a non-negative age always fits the cast
+    Ok(age.max(0) as u64)
+}
+
+/// `conf * 10_000 / |price|`, rounded down; None for a zero price or on overflow.
+pub fn conf_ratio_bps(conf: u64, price: i64) -> Option<u128> {
+    // u128 so conf * 10_000 fits for any u64 conf; checked anyway so a future widening can't wrap
+    u128::from(conf)
+        .checked_mul(10_000)?
+        .checked_div(u128::from(price.unsigned_abs()))
+}
+
+impl From<ValidationError> for Error {
+    fn from(e: ValidationError) -> Self {
+        match e {
+            ValidationError::InvalidArgs => error!(ErrorCode::InvalidArgs),
+            ValidationError::PriceTooOld => error!(ErrorCode::PriceTooOld),
+            ValidationError::FuturePrice => error!(ErrorCode::FuturePrice),
+            ValidationError::ZeroPrice => error!(ErrorCode::ZeroPrice),
+            ValidationError::WideConfidence => error!(ErrorCode::WideConfidence),
+            ValidationError::MathOverflow => error!(ErrorCode::MathOverflow),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+    const MAX_AGE: u64 = 60;
+    const MAX_CONF_BPS: u64 = 200;
+
+    /// A 2000.00000000 price with 0.1% conf, published `age` seconds before NOW.
+    fn validate_aged(age: i64) -> core::result::Result<(), ValidationError> {
+        validate_observation(200_000_000_000, 200_000_000, -8, NOW - age, NOW, MAX_AGE, MAX_CONF_BPS)
+    }
+
+    fn validate_priced(price: i64, conf: u64) -> core::result::Result<(), ValidationError> {
+        validate_observation(price, conf, -8, NOW, NOW, MAX_AGE, MAX_CONF_BPS)
+    }
+
+    #[test]
+    fn accepts_ages_up_to_max_age() {
+        assert_eq!(validate_aged(0), Ok(()));
+        assert_eq!(validate_aged(1), Ok(()));
+        assert_eq!(validate_aged(MAX_AGE as i64 - 1), Ok(()));
+        assert_eq!(validate_aged(MAX_AGE as i64), Ok(()));
+    }
+
+    #[test]
+    fn rejects_ages_past_max_age() {
+        assert_eq!(validate_aged(MAX_AGE as i64 + 1), Err(ValidationError::PriceTooOld));
+        assert_eq!(validate_aged(3_600), Err(ValidationError::PriceTooOld));
+        assert_eq!(
+            validate_observation(1, 0, 0, i64::MIN, i64::MAX, 1, 0),
+            Err(ValidationError::PriceTooOld)
+        );
+    }
+
+    #[test]
+    fn bounds_future_publish_times() {
+        assert_eq!(validate_aged(-MAX_FUTURE_SKEW_SECS), Ok(()));
+        assert_eq!(validate_aged(-MAX_FUTURE_SKEW_SECS - 1), Err(ValidationError::FuturePrice));
+        assert_eq!(
+            validate_observation(1, 0, 0, i64::MAX, i64::MIN, u64::MAX, 0),
+            Err(ValidationError::FuturePrice)
+        );
+    }
+
+    #[test]
+    fn rejects_zero_max_age() {
+        assert_eq!(
+            validate_observation(1, 0, 0, NOW, NOW, 0, MAX_CONF_BPS),
+            Err(ValidationError::InvalidArgs)
+        );
+    }
+
+    #[test]
+    fn rejects_zero_price() {
+        assert_eq!(validate_priced(0, 0), Err(ValidationError::ZeroPrice));
+    }
+
+    #[test]
+    fn judges_negative_prices_by_magnitude() {
+        assert_eq!(validate_priced(-10_000, 200), Ok(()));
+        assert_eq!(validate_priced(-10_000, 201), Err(ValidationError::WideConfidence));
+        assert_eq!(validate_priced(i64::MIN, 1), Ok(()));
+    }
+
+    #[test]
+    fn caps_the_confidence_ratio() {
+        // 200 bps of 10_000 is exactly 200
+        assert_eq!(validate_priced(10_000, 200), Ok(()));
+        assert_eq!(validate_priced(10_000, 201), Err(ValidationError::WideConfidence));
+        // ratio rounds down, so 2.0099% still passes a 2% cap
+        assert_eq!(validate_priced(1_000_000, 20_099), Ok(()));
+        assert_eq!(validate_priced(1, u64::MAX), Err(ValidationError::WideConfidence));
+        assert_eq!(validate_priced(i64::MAX, 0), Ok(()));
+    }
+
+    #[test]
+    fn maximal_conf_is_rejected_without_wrapping() {
+        // u64::MAX * 10_000 would wrap a u64; in u128 it is a plain ratio
+        assert_eq!(validate_priced(i64::MAX, u64::MAX), Err(ValidationError::WideConfidence));
+        assert_eq!(
+            validate_observation(i64::MIN, u64::MAX, -8, NOW, NOW, MAX_AGE, u64::MAX),
+            Ok(())
+        );
+        assert_eq!(Error::from(ValidationError::MathOverflow), ErrorCode::MathOverflow.into());
+    }
+
+    #[test]
+    fn zero_conf_cap_disables_the_check() {
+        let validate =
+            |conf, max_conf_bps| validate_observation(10_000, conf, -8, NOW, NOW, MAX_AGE, max_conf_bps);
+        assert_eq!(validate(u64::MAX, 0), Ok(()));
+        assert_eq!(validate(1, 0), Ok(()));
+        assert_eq!(validate(1, 1), Ok(()));
+        assert_eq!(validate(2, 1), Err(ValidationError::WideConfidence));
+        // the zero-price guard still applies
+        assert_eq!(
+            validate_observation(0, 0, -8, NOW, NOW, MAX_AGE, 0),
+            Err(ValidationError::ZeroPrice)
+        );
+    }
+
+    #[test]
+    fn ratio_is_reported_in_bps() {
+        assert_eq!(conf_ratio_bps(200, 10_000), Some(200));
+        assert_eq!(conf_ratio_bps(200, -10_000), Some(200));
+        assert_eq!(conf_ratio_bps(u64::MAX, 1), Some(u128::from(u64::MAX) * 10_000));
+        assert_eq!(conf_ratio_bps(1, 0), None);
+    }
+
+    #[test]
+    fn freshness_is_checked_before_confidence() {
+        assert_eq!(
+            validate_observation(0, u64::MAX, -8, NOW - 3_600, NOW, MAX_AGE, MAX_CONF_BPS),
+            Err(ValidationError::PriceTooOld)
+        );
+    }
+
+    #[test]
+    fn ages_are_computed_without_wrapping() {
+        assert_eq!(observation_age(NOW - 30, NOW), Ok(30));
+        assert_eq!(observation_age(NOW, NOW), Ok(0));
+        assert_eq!(observation_age(i64::MIN, i64::MAX), Ok(u64::MAX));
+        // within the tolerated skew the observation is simply current
+        assert_eq!(observation_age(NOW + MAX_FUTURE_SKEW_SECS, NOW), Ok(0));
+    }
+
+    #[test]
+    fn far_future_publish_times_are_an_error_not_a_fresh_age() {
+        // as a u64 subtraction this would wrap to an age of about 2^64 - 1 or, worse, look fresh
+        assert_eq!(observation_age(NOW + MAX_FUTURE_SKEW_SECS + 1, NOW), Err(ValidationError::FuturePrice));
+        assert_eq!(observation_age(i64::MAX, NOW), Err(ValidationError::FuturePrice));
+        assert_eq!(observation_age(i64::MAX, i64::MIN), Err(ValidationError::FuturePrice));
+        assert_eq!(
+            validate_observation(1, 0, 0, i64::MAX, NOW, u64::MAX, 0),
+            Err(ValidationError::FuturePrice)
+        );
+    }
+
+    #[test]
+    fn converts_slots_at_400ms() {
+        assert_eq!(slots_to_secs(0), 0);
+        assert_eq!(slots_to_secs(1), 1);
+        assert_eq!(slots_to_secs(5), 2);
+        assert_eq!(slots_to_secs(150), 60);
+        assert_eq!(slots_to_secs(151), 61);
+        // 0.4 * u64::MAX, rounded up, still fits
+        assert_eq!(slots_to_secs(u64::MAX), 7_378_697_629_483_820_646);
+    }
+
+    #[test]
+    fn maps_to_program_errors() {
+        assert_eq!(Error::from(ValidationError::PriceTooOld), ErrorCode::PriceTooOld.into());
+        assert_eq!(Error::from(ValidationError::WideConfidence), ErrorCode::WideConfidence.into());
+        assert_eq!(Error::from(ValidationError::FuturePrice), ErrorCode::FuturePrice.into());
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn validation_never_panics(
+            price in any::<i64>(),
+            conf in any::<u64>(),
+            exponent in any::<i32>(),
+            publish_time in any::<i64>(),
+            now in any::<i64>(),
+            max_age in any::<u64>(),
+            max_conf_bps in any::<u64>(),
+        ) {
+            let _ = validate_observation(price, conf, exponent, publish_time, now, max_age, max_conf_bps);
+        }
+
+        #[test]
+        fn fresh_ages_agree_with_validation(
+            publish_time in any::<i64>(),
+            now in any::<i64>(),
+            max_age in 1..=u64::MAX,
+        ) {
+            // An age within max_age is exactly what validate_observation accepts (given a usable price)
+            let fresh = observation_age(publish_time, now).is_ok_and(|age| age <= max_age);
+            prop_assert_eq!(fresh, validate_observation(1, 0, 0, publish_time, now, max_age, 0).is_ok());
+        }
+
+        #[test]
+        fn ratio_is_exact_up_to_rounding_down(conf in any::<u64>(), price in any::<i64>()) {
+            let Some(ratio) = conf_ratio_bps(conf, price) else {
+                // only a zero price has no ratio; nothing here can overflow
+                prop_assert_eq!(price, 0);
+                return Ok(());
+            };
+            // ratio = floor(conf * 10_000 / |price|): below by less than one bps
+            let scaled = u128::from(conf) * 10_000;
+            let magnitude = u128::from(price.unsigned_abs());
+            prop_assert!(ratio * magnitude <= scaled);
+            prop_assert!(scaled < (ratio + 1) * magnitude);
+        }
+
+        #[test]
+        fn the_sign_of_the_price_does_not_change_the_verdict(
+            price in (i64::MIN + 1)..=i64::MAX,
+            conf in any::<u64>(),
+            max_conf_bps in any::<u64>(),
+        ) {
+            let validate = |price| validate_observation(price, conf, -8, 0, 0, 60, max_conf_bps);
+            prop_assert_eq!(validate(price), validate(-price));
+        }
+    }
+}
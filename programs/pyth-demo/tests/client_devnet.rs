@@ -0,0 +1,59 @@
+//! Devnet checks for the `client` module. The RPC tests only run when `PYTH_DEMO_DEVNET_RPC` is set,
+//! e.g. `PYTH_DEMO_DEVNET_RPC=https://api.devnet.solana.com cargo test -p pyth-demo --features client`.
+#![cfg(feature = "client")]
+
+use std::str::FromStr;
+
+use pyth_demo::client::{fetch_price_update_account, price_feed_address, send_read_price};
+use pyth_demo::ReadPriceArgs;
+use pyth_solana_receiver_sdk::price_update::get_feed_id_from_hex;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::read_keypair_file;
+
+const ETH_USD_FEED_ID_HEX: &str = "0xff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace";
+const ETH_USD_PRICE_UPDATE: &str = "42amVS4KgzR9rA28tkVYqVXjq9Qa8dcZQMbH5EYFX6XC";
+
+fn devnet() -> Option<RpcClient> {
+    std::env::var("PYTH_DEMO_DEVNET_RPC").ok().map(RpcClient::new)
+}
+
+#[test]
+fn derives_the_sponsored_feed_address() {
+    let feed_id = get_feed_id_from_hex(ETH_USD_FEED_ID_HEX).unwrap();
+    assert_eq!(
+        price_feed_address(0, &feed_id),
+        Pubkey::from_str(ETH_USD_PRICE_UPDATE).unwrap()
+    );
+}
+
+#[test]
+fn fetches_the_eth_usd_price_update() {
+    let Some(rpc) = devnet() else { return };
+    let address = fetch_price_update_account(&rpc, ETH_USD_FEED_ID_HEX).unwrap();
+    assert_eq!(address, Pubkey::from_str(ETH_USD_PRICE_UPDATE).unwrap());
+}
+
+/// Also needs `PYTH_DEMO_PAYER`, a funded devnet keypair file, and the program deployed at its
+/// declared id.
+#[test]
+fn sends_read_price() {
+    let Some(rpc) = devnet() else { return };
+    let Ok(payer) = std::env::var("PYTH_DEMO_PAYER") else { return };
+    let payer = read_keypair_file(payer).unwrap();
+
+    let price_update = fetch_price_update_account(&rpc, ETH_USD_FEED_ID_HEX).unwrap();
+    let args = ReadPriceArgs {
+        feed_id: get_feed_id_from_hex(ETH_USD_FEED_ID_HEX).unwrap(),
+        feed_id_hex: String::new(),
+        max_age_secs: 60,
+        max_conf_ratio_bps: 200,
+        max_abs_conf: 0,
+        min_publishers: 0,
+        max_deviation_bps: 0,
+        verbose: true,
+        reject_negative: true,
+        max_publish_time: 0,
+    };
+    send_read_price(&rpc, &payer, &pyth_demo::ID, &price_update, args).unwrap();
+}
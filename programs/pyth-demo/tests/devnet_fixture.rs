@@ -0,0 +1,20 @@
+//! Decodes a real `PriceUpdateV2` account captured from devnet, rather than bytes laid out from
+//! the receiver's source. `tests/fixtures/README.md` has the commands that capture it.
+#![cfg(feature = "devnet-fixture")]
+
+use pyth_demo::decode_price_update;
+use pyth_solana_receiver_sdk::price_update::{get_feed_id_from_hex, PriceUpdateV2, VerificationLevel};
+
+const ETH_USD_FEED_ID_HEX: &str = "0xff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace";
+/// `getAccountInfo` data of 42amVS4KgzR9rA28tkVYqVXjq9Qa8dcZQMbH5EYFX6XC on devnet
+const ETH_USD_PRICE_UPDATE: &[u8] = include_bytes!("fixtures/eth_usd_price_update.bin");
+
+#[test]
+fn decodes_a_captured_devnet_account() {
+    assert_eq!(ETH_USD_PRICE_UPDATE.len(), PriceUpdateV2::LEN);
+
+    let update = decode_price_update(ETH_USD_PRICE_UPDATE).unwrap();
+    assert_eq!(update.price_message.feed_id, get_feed_id_from_hex(ETH_USD_FEED_ID_HEX).unwrap());
+    assert_eq!(update.price_message.exponent, -8);
+    assert_eq!(update.verification_level, VerificationLevel::Full);
+}
@@ -0,0 +1,54 @@
+//! Decimal rendering of `(price, exponent)` pairs by `client::format_price`.
+#![cfg(feature = "client")]
+
+use pyth_demo::client::{format_price, ClientError, MAX_FORMAT_EXPONENT};
+
+#[test]
+fn places_the_point_for_negative_exponents() {
+    assert_eq!(format_price(182_345, -2).unwrap(), "1823.45");
+    assert_eq!(format_price(254_123_456_789, -8).unwrap(), "2541.23456789");
+    // fewer digits than the scale still get an integer zero
+    assert_eq!(format_price(5, -3).unwrap(), "0.005");
+    assert_eq!(format_price(123, -3).unwrap(), "0.123");
+}
+
+#[test]
+fn keeps_every_digit_without_rounding() {
+    assert_eq!(format_price(182_300, -2).unwrap(), "1823.00");
+    assert_eq!(format_price(99_999_999, -8).unwrap(), "0.99999999");
+    assert_eq!(format_price(i64::MAX, -4).unwrap(), "922337203685477.5807");
+}
+
+#[test]
+fn renders_exponent_zero_and_positive_exponents_as_integers() {
+    assert_eq!(format_price(182_345, 0).unwrap(), "182345");
+    assert_eq!(format_price(182_345, 3).unwrap(), "182345000");
+    assert_eq!(format_price(-7, 2).unwrap(), "-700");
+}
+
+#[test]
+fn renders_zero_without_a_sign() {
+    assert_eq!(format_price(0, 0).unwrap(), "0");
+    assert_eq!(format_price(0, 5).unwrap(), "0");
+    assert_eq!(format_price(0, -2).unwrap(), "0.00");
+}
+
+#[test]
+fn puts_the_sign_before_the_integer_part() {
+    assert_eq!(format_price(-182_345, -2).unwrap(), "-1823.45");
+    assert_eq!(format_price(-5, -3).unwrap(), "-0.005");
+    assert_eq!(format_price(i64::MIN, -8).unwrap(), "-92233720368.54775808");
+}
+
+#[test]
+fn rejects_an_exponent_past_the_cap() {
+    let max = MAX_FORMAT_EXPONENT as i32;
+    assert_eq!(format_price(1, max).unwrap().len(), max as usize + 1);
+    assert_eq!(format_price(1, -max).unwrap().len(), max as usize + 2);
+    for exponent in [max + 1, -max - 1, i32::MAX, i32::MIN] {
+        assert!(matches!(
+            format_price(1, exponent),
+            Err(ClientError::ExponentOutOfRange(e)) if e == exponent
+        ));
+    }
+}
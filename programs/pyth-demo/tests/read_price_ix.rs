@@ -0,0 +1,114 @@
+//! An in-process substitute for running `client::read_price_ix` under litesvm or a validator: the
+//! instruction goes through the program's entrypoint natively, with the clock and return data
+//! syscalls stubbed, so no SBF build is needed.
+//!
+//! It is not the built `.so` under the real runtime. The runtime's own checks (that signers signed,
+//! that only the owner wrote to an account, account serialization and compute limits) are skipped;
+//! only what the program itself checks is exercised. `tests/litesvm` runs the same instruction
+//! through the built `.so`, and the TypeScript suite covers the deployed build.
+#![cfg(all(feature = "client", feature = "test-utils"))]
+
+use std::sync::Mutex;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program_stubs::{set_syscall_stubs, SyscallStubs};
+use pyth_demo::client::read_price_ix;
+use pyth_demo::test_utils::make_price_update;
+use pyth_demo::{decode_validated_price, ReadPriceArgs};
+
+const FEED_ID: [u8; 32] = [0x42; 32];
+const PUBLISH_TIME: i64 = 1_760_000_000;
+
+static RETURN_DATA: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+/// A clock five seconds past the fixture, and a sink for `set_return_data`.
+struct Stubs;
+
+impl SyscallStubs for Stubs {
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = Clock {
+            unix_timestamp: PUBLISH_TIME + 5,
+            ..Clock::default()
+        };
+        unsafe { *(var_addr as *mut Clock) = clock };
+        0
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        *RETURN_DATA.lock().unwrap() = data.to_vec();
+    }
+}
+
+/// Run `read_price_ix` against the fixture, stored under `price_update_owner`.
+fn run_read_price(price_update_owner: Pubkey) -> core::result::Result<(), ProgramError> {
+    set_syscall_stubs(Box::new(Stubs));
+
+    let price_update = Pubkey::new_unique();
+    let args = ReadPriceArgs {
+        feed_id: FEED_ID,
+        feed_id_hex: String::new(),
+        max_age_secs: 60,
+        max_conf_ratio_bps: 200,
+        max_abs_conf: 0,
+        min_publishers: 0,
+        max_deviation_bps: 0,
+        verbose: true,
+        reject_negative: true,
+        max_publish_time: 0,
+    };
+    let ix = read_price_ix(pyth_demo::ID, price_update, args);
+    assert_eq!(ix.accounts[0].pubkey, price_update);
+
+    // One backing account per meta, owned the way a cluster would own it
+    let system = anchor_lang::system_program::ID;
+    let mut backing: Vec<(u64, Vec<u8>, Pubkey)> = ix
+        .accounts
+        .iter()
+        .map(|meta| match meta.pubkey {
+            key if key == price_update => {
+                let data = make_price_update(FEED_ID, 254_123_456_789, 150_000_000, -8, PUBLISH_TIME);
+                (1_000_000, data, price_update_owner)
+            },
+            key if key == pyth_demo::ID => (1, Vec::new(), Pubkey::default()),
+            // the config and allowlist PDAs, not created yet
+            _ => (0, Vec::new(), system),
+        })
+        .collect();
+    let infos: Vec<AccountInfo> = ix
+        .accounts
+        .iter()
+        .zip(backing.iter_mut())
+        .map(|(meta, (lamports, data, owner))| {
+            AccountInfo::new(
+                &meta.pubkey,
+                meta.is_signer,
+                meta.is_writable,
+                lamports,
+                data,
+                owner,
+                meta.pubkey == pyth_demo::ID,
+                0,
+            )
+        })
+        .collect();
+
+    pyth_demo::entry(&ix.program_id, &infos, &ix.data)
+}
+
+#[test]
+fn read_price_ix_round_trips_through_the_program() {
+    run_read_price(pyth_solana_receiver_sdk::ID).unwrap();
+
+    let returned = decode_validated_price(&RETURN_DATA.lock().unwrap()).unwrap();
+    assert_eq!(returned.feed_id, FEED_ID);
+    assert_eq!(returned.price, 254_123_456_789);
+    assert_eq!(returned.exponent, -8);
+    assert_eq!(returned.publish_time, PUBLISH_TIME);
+}
+
+#[test]
+fn rejects_a_price_update_with_the_wrong_owner() {
+    // Byte-identical data, just not written by the receiver
+    let spoofed: ProgramError = Error::from(pyth_demo::ErrorCode::InvalidPriceAccountOwner).into();
+    assert_eq!(run_read_price(pyth_demo::ID), Err(spoofed));
+}
@@ -0,0 +1,2 @@
+//! No code: the litesvm checks live in `tests/`. Run `anchor build` first, then
+//! `cargo test --manifest-path tests/litesvm/Cargo.toml`.
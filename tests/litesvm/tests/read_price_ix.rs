@@ -0,0 +1,92 @@
+//! `client::read_price_ix` sent through the built `target/deploy/pyth_demo.so` under litesvm, so the
+//! runtime's own checks (signatures, account ownership, serialization, compute) run too. Build the
+//! program with `anchor build` first.
+
+use anchor_lang::prelude::Clock;
+use litesvm::types::TransactionResult;
+use litesvm::LiteSVM;
+use pyth_demo::client::read_price_ix;
+use pyth_demo::test_utils::make_price_update;
+use pyth_demo::{decode_validated_price, ErrorCode, ReadPriceArgs};
+use solana_sdk::account::Account;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+const PROGRAM_SO: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../target/deploy/pyth_demo.so");
+const FEED_ID: [u8; 32] = [0x42; 32];
+const PUBLISH_TIME: i64 = 1_760_000_000;
+
+/// A VM with the program loaded, the clock five seconds past the fixture, and the fixture stored
+/// under `price_update_owner`.
+fn setup(price_update_owner: Pubkey) -> (LiteSVM, Keypair, Pubkey) {
+    let mut svm = LiteSVM::new();
+    svm.add_program_from_file(pyth_demo::ID, PROGRAM_SO)
+        .expect("target/deploy/pyth_demo.so is missing; run `anchor build` first");
+
+    let mut clock = svm.get_sysvar::<Clock>();
+    clock.unix_timestamp = PUBLISH_TIME + 5;
+    svm.set_sysvar::<Clock>(&clock);
+
+    let price_update = Pubkey::new_unique();
+    let data = make_price_update(FEED_ID, 254_123_456_789, 150_000_000, -8, PUBLISH_TIME);
+    let account = Account {
+        lamports: svm.minimum_balance_for_rent_exemption(data.len()),
+        data,
+        owner: price_update_owner,
+        executable: false,
+        rent_epoch: 0,
+    };
+    svm.set_account(price_update, account).unwrap();
+
+    let payer = Keypair::new();
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    (svm, payer, price_update)
+}
+
+fn args() -> ReadPriceArgs {
+    ReadPriceArgs {
+        feed_id: FEED_ID,
+        feed_id_hex: String::new(),
+        max_age_secs: 60,
+        max_conf_ratio_bps: 200,
+        max_abs_conf: 0,
+        min_publishers: 0,
+        max_deviation_bps: 0,
+        verbose: true,
+        reject_negative: true,
+        max_publish_time: 0,
+    }
+}
+
+fn send_read_price(svm: &mut LiteSVM, payer: &Keypair, price_update: Pubkey) -> TransactionResult {
+    let ix = read_price_ix(pyth_demo::ID, price_update, args());
+    let blockhash = svm.latest_blockhash();
+    let tx = Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[payer], blockhash);
+    svm.send_transaction(tx)
+}
+
+#[test]
+fn read_price_ix_returns_the_versioned_price() {
+    let (mut svm, payer, price_update) = setup(pyth_solana_receiver_sdk::ID);
+    let meta = send_read_price(&mut svm, &payer, price_update).unwrap();
+
+    assert_eq!(meta.return_data.program_id, pyth_demo::ID);
+    let returned = decode_validated_price(&meta.return_data.data).unwrap();
+    assert_eq!(returned.feed_id, FEED_ID);
+    assert_eq!(returned.price, 254_123_456_789);
+    assert_eq!(returned.conf, 150_000_000);
+    assert_eq!(returned.exponent, -8);
+    assert_eq!(returned.publish_time, PUBLISH_TIME);
+}
+
+#[test]
+fn rejects_a_price_update_with_the_wrong_owner() {
+    // Byte-identical data, just not written by the receiver
+    let (mut svm, payer, price_update) = setup(Pubkey::new_unique());
+    let failed = send_read_price(&mut svm, &payer, price_update).unwrap_err();
+
+    let code = u32::from(ErrorCode::InvalidPriceAccountOwner);
+    assert_eq!(failed.err, TransactionError::InstructionError(0, InstructionError::Custom(code)));
+}
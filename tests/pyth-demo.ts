@@ -1,16 +1,1100 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
+import { ComputeBudgetProgram, Keypair, PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
 import { PythDemo } from "../target/types/pyth_demo";
+import { PriceConsumer } from "../target/types/price_consumer";
+
+// Sponsored ETH/USD PriceUpdateV2 account, cloned from devnet (see Anchor.toml)
+const ETH_USD_PRICE_UPDATE = new PublicKey("42amVS4KgzR9rA28tkVYqVXjq9Qa8dcZQMbH5EYFX6XC");
+const ETH_USD_FEED_ID_HEX = "0xff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace";
+const ETH_USD_FEED_ID = Array.from(Buffer.from(ETH_USD_FEED_ID_HEX.slice(2), "hex"));
+
+// The cloned account is a snapshot, so freshness is effectively disabled in these tests
+const ANY_AGE = new anchor.BN(10 * 365 * 24 * 60 * 60);
+const readArgs = {
+  feedId: ETH_USD_FEED_ID,
+  feedIdHex: "",
+  maxAgeSecs: ANY_AGE,
+  maxConfRatioBps: new anchor.BN(10_000),
+  maxAbsConf: new anchor.BN(0),
+  minPublishers: 0,
+  maxDeviationBps: new anchor.BN(0),
+  verbose: true,
+  rejectNegative: true,
+  maxPublishTime: new anchor.BN(0),
+};
+
+// Capture groups of the `price=…, conf=…, exponent=…, t=…` log line with `prefix`
+function findPriceLog(logs: string[], prefix: string): string[] | undefined {
+  const re = new RegExp(`^Program log: ${prefix}price=(-?\\d+), conf=(\\d+), exponent=(-?\\d+), t=(-?\\d+)`);
+  for (const line of logs) {
+    const m = line.match(re);
+    if (m) return m.slice(1);
+  }
+  return undefined;
+}
+
+// Compute units `programId` reported consuming in a simulation's logs
+function unitsConsumed(logs: string[], programId: PublicKey): number {
+  const re = new RegExp(`^Program ${programId.toBase58()} consumed (\\d+) of`);
+  for (const line of logs) {
+    const m = line.match(re);
+    if (m) return Number(m[1]);
+  }
+  throw new Error("no compute units logged");
+}
+
+// Await `p` and assert it fails with the program error `code`
+async function expectAnchorError(p: Promise<unknown>, code: string) {
+  let err: unknown;
+  try {
+    await p;
+  } catch (e) {
+    err = e;
+  }
+  expect(err, `expected ${code}`).to.be.instanceOf(anchor.AnchorError);
+  expect((err as anchor.AnchorError).error.errorCode.code).to.equal(code);
+}
 
 describe("pyth-demo", () => {
   // Configure the client to use the local cluster.
   anchor.setProvider(anchor.AnchorProvider.env());
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
 
   const program = anchor.workspace.pythDemo as Program<PythDemo>;
+  const consumer = anchor.workspace.priceConsumer as Program<PriceConsumer>;
+  const admin = provider.wallet.publicKey;
+  const [configPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("config")],
+    program.programId
+  );
+  const [allowlistPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("allowlist")],
+    program.programId
+  );
+
+  describe("config", () => {
+    it("initializes with the signer as admin", async () => {
+      await program.methods
+        .initializeConfig(new anchor.BN(60), new anchor.BN(200))
+        .accountsPartial({ admin })
+        .rpc();
+
+      const config = await program.account.oracleConfig.fetch(configPda);
+      expect(config.admin.toBase58()).to.equal(admin.toBase58());
+      expect(config.maxAgeSecs.toNumber()).to.equal(60);
+      expect(config.maxConfRatioBps.toNumber()).to.equal(200);
+    });
+
+    it("lets the admin update thresholds", async () => {
+      await program.methods
+        .updateConfig(new anchor.BN(30), new anchor.BN(100))
+        .accountsPartial({ admin })
+        .rpc();
+
+      const config = await program.account.oracleConfig.fetch(configPda);
+      expect(config.maxAgeSecs.toNumber()).to.equal(30);
+      expect(config.maxConfRatioBps.toNumber()).to.equal(100);
+    });
+
+    it("rejects updates from a non-admin", async () => {
+      const intruder = Keypair.generate();
+      await expectAnchorError(
+        program.methods
+          .updateConfig(new anchor.BN(1), new anchor.BN(10_000))
+          .accountsPartial({ admin: intruder.publicKey })
+          .signers([intruder])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+
+    it("applies a per-feed max age override, then falls back to the global one", async () => {
+      const read = () =>
+        program.methods
+          .readPrice(readArgs)
+          .accountsPartial({ priceUpdate: ETH_USD_PRICE_UPDATE, allowlist: allowlistPda, config: configPda });
+      await program.methods.setMaxAgeOverride(ETH_USD_FEED_ID, ANY_AGE).accountsPartial({ admin }).rpc();
+
+      const config = await program.account.oracleConfig.fetch(configPda);
+      expect(config.maxAgeOverrides).to.have.length(1);
+      expect(config.maxAgeSecs.toNumber()).to.equal(30);
+      // The snapshot is far older than the 30s global window, so only the override lets it through
+      const { price: { price } } = await read().view();
+      expect(price.toNumber()).to.be.greaterThan(0);
+
+      await program.methods.clearMaxAgeOverride(ETH_USD_FEED_ID).accountsPartial({ admin }).rpc();
+      await expectAnchorError(read().rpc(), "PriceTooOld");
+    });
+
+    it("rejects clearing an override that was never set", async () => {
+      await expectAnchorError(
+        program.methods.clearMaxAgeOverride(ETH_USD_FEED_ID).accountsPartial({ admin }).rpc(),
+        "OverrideNotFound"
+      );
+    });
+
+    it("holds every read to the config, with no way to leave it out", async () => {
+      // The args allow any age, but the PDA is pinned, so a stand-in account can't replace it
+      await expectAnchorError(
+        program.methods
+          .readPrice(readArgs)
+          .accountsPartial({ priceUpdate: ETH_USD_PRICE_UPDATE, allowlist: allowlistPda, config: allowlistPda })
+          .rpc(),
+        "ConstraintSeeds"
+      );
+    });
+
+    after(async () => {
+      // The suites below read the cloned snapshot, which only an override keeps inside the window
+      await program.methods.setMaxAgeOverride(ETH_USD_FEED_ID, ANY_AGE).accountsPartial({ admin }).rpc();
+    });
+  });
+
+  describe("healthcheck", () => {
+    it("reports the configured thresholds", async () => {
+      const health = await program.methods.healthcheck().view();
+      expect(health.configured).to.equal(true);
+      expect(health.maxAgeSecs.toNumber()).to.equal(30);
+      expect(health.maxConfRatioBps.toNumber()).to.equal(100);
+      expect(health.version).to.equal("0.1.0");
+      // Built without a network feature, so the mainnet defaults apply
+      expect(health.network).to.equal("mainnet");
+      expect(health.defaultFeedId).to.deep.equal(ETH_USD_FEED_ID);
+      expect(health.slot.toNumber()).to.be.greaterThan(0);
+    });
+
+    it("can't be pointed away from the config to report the defaults", async () => {
+      // The defaults are only for a deployment whose config PDA was never initialized
+      await expectAnchorError(
+        program.methods.healthcheck().accountsPartial({ config: allowlistPda }).rpc(),
+        "ConstraintSeeds"
+      );
+    });
+  });
+
+  describe("allowlist", () => {
+    const feedId = Array.from(Buffer.alloc(32, 7));
+
+    it("lets the admin add and remove feeds", async () => {
+      await program.methods.addFeed(feedId).accountsPartial({ admin }).rpc();
+      let allowlist = await program.account.allowlist.fetch(allowlistPda);
+      expect(allowlist.feedIds).to.deep.include(feedId);
+
+      await program.methods.removeFeed(feedId).accountsPartial({ admin }).rpc();
+      allowlist = await program.account.allowlist.fetch(allowlistPda);
+      expect(allowlist.feedIds).to.not.deep.include(feedId);
+    });
+
+    it("rejects removing a feed that is not listed", async () => {
+      await expectAnchorError(
+        program.methods.removeFeed(feedId).accountsPartial({ admin }).rpc(),
+        "FeedNotAllowed"
+      );
+    });
+
+    it("rejects a non-admin", async () => {
+      const intruder = Keypair.generate();
+      await expectAnchorError(
+        program.methods
+          .addFeed(feedId)
+          .accountsPartial({ admin: intruder.publicKey })
+          .signers([intruder])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+  });
+
+  describe("cpi", () => {
+    before(async () => {
+      // The allowlist exists after the tests above, so ETH/USD must be on it
+      await program.methods.addFeed(ETH_USD_FEED_ID).accountsPartial({ admin }).rpc();
+    });
+
+    it("returns the validated price to a calling program", async () => {
+      const sig = await consumer.methods
+        .consumePrice(readArgs)
+        .accountsPartial({
+          priceUpdate: ETH_USD_PRICE_UPDATE,
+          config: configPda,
+          allowlist: allowlistPda,
+          pythDemoProgram: program.programId,
+        })
+        .rpc({ commitment: "confirmed" });
+
+      const tx = await provider.connection.getTransaction(sig, {
+        commitment: "confirmed",
+        maxSupportedTransactionVersion: 0,
+      });
+      const logs = tx?.meta?.logMessages ?? [];
+      const logged = findPriceLog(logs, "");
+      const returned = findPriceLog(logs, "returned ");
+
+      expect(logged, "read_price log").to.not.be.undefined;
+      expect(returned, "consumer log").to.deep.equal(logged);
+    });
+
+    it("hands the price over through a scratch account", async () => {
+      const [reader] = PublicKey.findProgramAddressSync([Buffer.from("reader")], consumer.programId);
+      const [scratch] = PublicKey.findProgramAddressSync(
+        [Buffer.from("scratch"), reader.toBuffer()],
+        program.programId
+      );
+      await program.methods.initScratch(reader).accountsPartial({ payer: admin }).rpc();
+
+      const sig = await consumer.methods
+        .consumePriceViaScratch(readArgs)
+        .accountsPartial({
+          priceUpdate: ETH_USD_PRICE_UPDATE,
+          config: configPda,
+          allowlist: allowlistPda,
+          scratch,
+          pythDemoProgram: program.programId,
+        })
+        .rpc({ commitment: "confirmed" });
+
+      const tx = await provider.connection.getTransaction(sig, {
+        commitment: "confirmed",
+        maxSupportedTransactionVersion: 0,
+      });
+      const read = findPriceLog(tx?.meta?.logMessages ?? [], "scratch ");
+      const stored = await program.account.priceScratch.fetch(scratch);
+      expect(stored.authority.toBase58()).to.equal(reader.toBase58());
+      expect(stored.price.feedId).to.deep.equal(ETH_USD_FEED_ID);
+      expect(read, "consumer log").to.deep.equal([
+        stored.price.price.toString(),
+        stored.price.conf.toString(),
+        stored.price.exponent.toString(),
+        stored.price.publishTime.toString(),
+      ]);
+    });
+
+    it("rejects a scratch write not signed by its authority", async () => {
+      const [reader] = PublicKey.findProgramAddressSync([Buffer.from("reader")], consumer.programId);
+      const intruder = Keypair.generate();
+      // The intruder has no scratch account, so point at the reader's
+      const [scratch] = PublicKey.findProgramAddressSync(
+        [Buffer.from("scratch"), reader.toBuffer()],
+        program.programId
+      );
+      await expectAnchorError(
+        program.methods
+          .readPriceInto(readArgs)
+          .accountsPartial({
+            priceUpdate: ETH_USD_PRICE_UPDATE,
+            allowlist: allowlistPda,
+            scratch,
+            authority: intruder.publicKey,
+          })
+          .signers([intruder])
+          .rpc(),
+        "ConstraintSeeds"
+      );
+    });
+  });
+
+  describe("symbol registry", () => {
+    // The registry supplies the feed, so the args leave both id fields empty
+    const symbolArgs = { ...readArgs, feedId: new Array(32).fill(0) };
+    const readBySymbol = (symbol: string, args = symbolArgs) =>
+      program.methods
+        .readBySymbol(symbol, args)
+        .accountsPartial({ priceUpdate: ETH_USD_PRICE_UPDATE, allowlist: allowlistPda });
+
+    before(async () => {
+      await program.methods.registerSymbol("ETH/USD", ETH_USD_FEED_ID).accountsPartial({ admin }).rpc();
+    });
+
+    it("reads the feed registered under a symbol", async () => {
+      const { version, price: res } = await readBySymbol("ETH/USD").view();
+      expect(version).to.equal(1);
+      expect(res.feedId).to.deep.equal(ETH_USD_FEED_ID);
+      expect(res.price.toNumber()).to.be.greaterThan(0);
+    });
+
+    it("rejects an unregistered symbol", async () => {
+      await expectAnchorError(readBySymbol("BTC/USD").rpc(), "UnknownSymbol");
+    });
+
+    it("rejects a feed id passed alongside the symbol", async () => {
+      await expectAnchorError(readBySymbol("ETH/USD", readArgs).rpc(), "InvalidArgs");
+    });
+
+    it("bounds the symbol length", async () => {
+      await expectAnchorError(
+        program.methods.registerSymbol("X".repeat(17), ETH_USD_FEED_ID).accountsPartial({ admin }).rpc(),
+        "SymbolTooLong"
+      );
+    });
+
+    it("forgets a symbol once it is unregistered", async () => {
+      await program.methods.registerSymbol("TMP/USD", ETH_USD_FEED_ID).accountsPartial({ admin }).rpc();
+      await program.methods.unregisterSymbol("TMP/USD").accountsPartial({ admin }).rpc();
+      await expectAnchorError(readBySymbol("TMP/USD").rpc(), "UnknownSymbol");
+    });
+
+    it("rejects a non-admin", async () => {
+      const intruder = Keypair.generate();
+      await expectAnchorError(
+        program.methods
+          .registerSymbol("BTC/USD", ETH_USD_FEED_ID)
+          .accountsPartial({ admin: intruder.publicKey })
+          .signers([intruder])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+  });
+
+  describe("read_price", () => {
+    const accounts = { priceUpdate: ETH_USD_PRICE_UPDATE, allowlist: allowlistPda };
+
+    it("returns the validated price", async () => {
+      const res = await program.methods.readPrice(readArgs).accountsPartial(accounts).view();
+      expect(res.version).to.equal(1);
+      expect(res.price.feedId).to.deep.equal(ETH_USD_FEED_ID);
+      expect(res.price.price.toNumber()).to.be.greaterThan(0);
+    });
+
+    it("still accepts a hex feed id, at a higher compute cost", async () => {
+      const hexArgs = { ...readArgs, feedId: new Array(32).fill(0), feedIdHex: ETH_USD_FEED_ID_HEX };
+      const raw = await program.methods.readPrice(readArgs).accountsPartial(accounts).simulate();
+      const hex = await program.methods.readPrice(hexArgs).accountsPartial(accounts).simulate();
+
+      const rawUnits = unitsConsumed([...raw.raw], program.programId);
+      const hexUnits = unitsConsumed([...hex.raw], program.programId);
+      console.log(`      read_price CU: raw id ${rawUnits}, hex id ${hexUnits} (saves ${hexUnits - rawUnits})`);
+      expect(rawUnits).to.be.lessThan(hexUnits);
+    });
+
+    it("skips the price log when not verbose, and saves compute", async () => {
+      const loud = await program.methods.readPrice(readArgs).accountsPartial(accounts).simulate();
+      const quiet = await program.methods
+        .readPrice({ ...readArgs, verbose: false })
+        .accountsPartial(accounts)
+        .simulate();
+
+      const loudUnits = unitsConsumed([...loud.raw], program.programId);
+      const quietUnits = unitsConsumed([...quiet.raw], program.programId);
+      console.log(`      read_price CU: verbose ${loudUnits}, quiet ${quietUnits} (saves ${loudUnits - quietUnits})`);
+      expect(findPriceLog([...quiet.raw], "")).to.be.undefined;
+      // The event is still emitted
+      expect(quiet.events.length).to.be.greaterThan(0);
+      expect(quiet.events.length).to.equal(loud.events.length);
+      expect(quietUnits).to.be.lessThan(loudUnits);
+    });
+
+    it("names both feeds when the account is for a different one", async () => {
+      // Allowed, so the read gets as far as comparing the account's feed with the requested one
+      const otherFeed = Array.from(Buffer.alloc(32, 7));
+      await program.methods.addFeed(otherFeed).accountsPartial({ admin }).rpc();
+      let err: unknown;
+      try {
+        await program.methods.readPrice({ ...readArgs, feedId: otherFeed }).accountsPartial(accounts).rpc();
+      } catch (e) {
+        err = e;
+      } finally {
+        await program.methods.removeFeed(otherFeed).accountsPartial({ admin }).rpc();
+      }
+
+      expect(err, "expected FeedIdMismatch").to.be.instanceOf(anchor.AnchorError);
+      expect((err as anchor.AnchorError).error.errorCode.code).to.equal("FeedIdMismatch");
+      const expected = `expected 0x${"07".repeat(32)}, account has ${ETH_USD_FEED_ID_HEX}`;
+      expect((err as anchor.AnchorError).logs.some((l) => l.includes(expected))).to.equal(true);
+    });
+
+    it("returns the raw components and a consistent fixed-point value", async () => {
+      for (const decimals of [0, 6, 8, 12]) {
+        const { version, price: full } = await program.methods
+          .readPriceFull(readArgs, decimals)
+          .accountsPartial(accounts)
+          .view();
+        expect(version).to.equal(1);
+        expect(full.feedId).to.deep.equal(ETH_USD_FEED_ID);
+        expect(full.decimals).to.equal(decimals);
+        // value_fixed = price * 10^(exponent + decimals), truncated toward zero
+        const shift = full.exponent + decimals;
+        const ten = new anchor.BN(10);
+        const expected =
+          shift >= 0 ? full.price.mul(ten.pow(new anchor.BN(shift))) : full.price.div(ten.pow(new anchor.BN(-shift)));
+        expect(full.valueFixed.toString()).to.equal(expected.toString());
+      }
+    });
+
+    it("pins reads to a window ending at max_publish_time", async () => {
+      const { price: { publishTime } } = await program.methods.readPrice(readArgs).accountsPartial(accounts).view();
+      const pinned = (maxPublishTime: anchor.BN) =>
+        program.methods.readPrice({ ...readArgs, maxPublishTime }).accountsPartial(accounts);
+
+      // the bound is inclusive
+      const { price } = await pinned(publishTime).view();
+      expect(price.publishTime.toNumber()).to.equal(publishTime.toNumber());
+      await expectAnchorError(pinned(publishTime.subn(1)).rpc(), "PriceTooNew");
+    });
+
+    it("rejects a price account the receiver does not own", async () => {
+      // The config PDA is owned by this program, which is exactly what a spoof would look like
+      await expectAnchorError(
+        program.methods.readPrice(readArgs).accountsPartial({ ...accounts, priceUpdate: configPda }).rpc(),
+        "InvalidPriceAccountOwner"
+      );
+    });
+
+    it("trips the absolute confidence cap separately from the ratio cap", async () => {
+      await expectAnchorError(
+        program.methods
+          .readPrice({ ...readArgs, maxAbsConf: new anchor.BN(1) })
+          .accountsPartial(accounts)
+          .rpc(),
+        "ConfidenceTooWide"
+      );
+    });
+
+    it("fails closed on a publisher minimum it cannot verify", async () => {
+      await expectAnchorError(
+        program.methods
+          .readPrice({ ...readArgs, minPublishers: 3 })
+          .accountsPartial(accounts)
+          .rpc(),
+        "TooFewPublishers"
+      );
+    });
+  });
+
+  describe("read_prices", () => {
+    const update = { pubkey: ETH_USD_PRICE_UPDATE, isWritable: false, isSigner: false };
+    const readPrices = (n: number, maxAgeSecs = ANY_AGE, maxStaleAllowed = 0) =>
+      program.methods
+        .readPrices(
+          {
+            feedIds: new Array(n).fill(ETH_USD_FEED_ID),
+            maxAgeSecs,
+            maxConfRatioBps: new anchor.BN(10_000),
+            rejectNegative: true,
+          },
+          maxStaleAllowed
+        )
+        .remainingAccounts(new Array(n).fill(update));
+    // A one-second window makes every copy of the snapshot stale
+    const TIGHT = new anchor.BN(1);
+
+    it("reports no stale feeds when all are fresh", async () => {
+      expect(await readPrices(3).view()).to.equal(0);
+    });
+
+    it("tolerates stale feeds up to the allowed count and marks them", async () => {
+      expect(await readPrices(3, TIGHT, 3).view()).to.equal(0b111);
+      expect(await readPrices(3, TIGHT, 255).view()).to.equal(0b111);
+      expect(await readPrices(1, TIGHT, 1).view()).to.equal(0b1);
+    });
+
+    it("rejects a batch with any feed off the allowlist", async () => {
+      const feedIds = [ETH_USD_FEED_ID, Array.from(Buffer.alloc(32, 7))];
+      await expectAnchorError(
+        program.methods
+          .readPrices(
+            { feedIds, maxAgeSecs: ANY_AGE, maxConfRatioBps: new anchor.BN(10_000), rejectNegative: true },
+            0
+          )
+          .remainingAccounts([update, update])
+          .rpc(),
+        "FeedNotAllowed"
+      );
+    });
+
+    it("reverts once more feeds are stale than allowed", async () => {
+      await expectAnchorError(readPrices(3, TIGHT, 2).rpc(), "TooManyStaleFeeds");
+      await expectAnchorError(readPrices(1, TIGHT, 0).rpc(), "TooManyStaleFeeds");
+    });
+
+    it("stays within the per-feed compute estimate", async () => {
+      const one = unitsConsumed([...(await readPrices(1).simulate()).raw], program.programId);
+      const many = unitsConsumed([...(await readPrices(8).simulate()).raw], program.programId);
+      const perFeed = (many - one) / 7;
+      console.log(`      read_prices CU: ${one} for 1 feed, ~${Math.ceil(perFeed)} per extra feed`);
+      // FEED_CU_ESTIMATE in the program
+      expect(perFeed).to.be.at.most(10_000);
+    });
+
+    it("fails early when the compute limit is too low", async () => {
+      await expectAnchorError(
+        readPrices(16)
+          .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 50_000 })])
+          .rpc(),
+        "InsufficientComputeBudget"
+      );
+    });
+  });
+
+  describe("basket_price", () => {
+    const update = { pubkey: ETH_USD_PRICE_UPDATE, isWritable: false, isSigner: false };
+    const basketArgs = (weights: number[]) => ({
+      feedIds: weights.map(() => ETH_USD_FEED_ID),
+      weights: weights.map((w) => new anchor.BN(w)),
+      maxAgeSecs: ANY_AGE,
+      maxConfRatioBps: new anchor.BN(10_000),
+      targetDecimals: 8,
+      rejectNegative: true,
+    });
+
+    it("weights the same feed back to its own price", async () => {
+      const res = await program.methods
+        .basketPrice(basketArgs([3, 1]))
+        .remainingAccounts([update, update])
+        .view();
+      const { price: { price, exponent } } = await program.methods
+        .readPrice(readArgs)
+        .accountsPartial({ priceUpdate: ETH_USD_PRICE_UPDATE, allowlist: allowlistPda })
+        .view();
+      // ETH/USD is published at exponent -8, so the 8-decimal basket is the raw price
+      expect(exponent).to.equal(-8);
+      expect(res.price.toString()).to.equal(price.toString());
+    });
+
+    it("rejects weights that sum to zero", async () => {
+      await expectAnchorError(
+        program.methods.basketPrice(basketArgs([0])).remainingAccounts([update]).rpc(),
+        "ZeroWeight"
+      );
+    });
+  });
+
+  describe("aggregate_prices", () => {
+    const update = { pubkey: ETH_USD_PRICE_UPDATE, isWritable: false, isSigner: false };
+    const aggregateArgs = (feeds: number, mode: object) => ({
+      feedIds: new Array(feeds).fill(ETH_USD_FEED_ID),
+      mode,
+      maxAgeSecs: ANY_AGE,
+      maxConfRatioBps: new anchor.BN(10_000),
+      targetDecimals: 8,
+      rejectNegative: true,
+    });
+
+    it("agrees with read_price in every mode for copies of one feed", async () => {
+      const { price: { price } } = await program.methods
+        .readPrice(readArgs)
+        .accountsPartial({ priceUpdate: ETH_USD_PRICE_UPDATE, allowlist: allowlistPda })
+        .view();
+      for (const mode of [{ min: {} }, { max: {} }, { median: {} }, { mean: {} }]) {
+        const res = await program.methods
+          .aggregatePrices(aggregateArgs(4, mode))
+          .remainingAccounts([update, update, update, update])
+          .view();
+        expect(res.price.toString(), JSON.stringify(mode)).to.equal(price.toString());
+        expect(res.decimals).to.equal(8);
+      }
+    });
+
+    it("rejects an empty feed set", async () => {
+      await expectAnchorError(
+        program.methods.aggregatePrices(aggregateArgs(0, { median: {} })).rpc(),
+        "NoFeeds"
+      );
+    });
+  });
+
+  describe("read_price_no_older_than_slots", () => {
+    const accounts = { priceUpdate: ETH_USD_PRICE_UPDATE, allowlist: allowlistPda };
+
+    it("accepts a slot window wider than the snapshot's age", async () => {
+      // ~10 years of 400ms slots; the seconds window is just as wide
+      const { price: res } = await program.methods
+        .readPriceNoOlderThanSlots(readArgs, ANY_AGE.muln(3))
+        .accountsPartial(accounts)
+        .view();
+      expect(res.feedId).to.deep.equal(ETH_USD_FEED_ID);
+    });
+
+    it("applies a narrow slot window even when the seconds window is wide", async () => {
+      await expectAnchorError(
+        program.methods
+          .readPriceNoOlderThanSlots(readArgs, new anchor.BN(1))
+          .accountsPartial(accounts)
+          .rpc(),
+        "PriceTooOld"
+      );
+    });
+  });
+
+  describe("read_price_in_range", () => {
+    const accounts = { priceUpdate: ETH_USD_PRICE_UPDATE, allowlist: allowlistPda };
+
+    it("passes a price inside inclusive bounds", async () => {
+      const { price: { price } } = await program.methods.readPrice(readArgs).accountsPartial(accounts).view();
+      const { price: res } = await program.methods
+        .readPriceInRange(readArgs, price, price)
+        .accountsPartial(accounts)
+        .view();
+      expect(res.price.toString()).to.equal(price.toString());
+    });
+
+    it("rejects a price outside the bounds", async () => {
+      const { price: { price } } = await program.methods.readPrice(readArgs).accountsPartial(accounts).view();
+      await expectAnchorError(
+        program.methods
+          .readPriceInRange(readArgs, price.addn(1), price.addn(100))
+          .accountsPartial(accounts)
+          .rpc(),
+        "PriceOutOfRange"
+      );
+    });
+  });
+
+  describe("read_price_with_slippage", () => {
+    const accounts = { priceUpdate: ETH_USD_PRICE_UPDATE, allowlist: allowlistPda };
+
+    it("passes at the expected price even with no slippage allowed", async () => {
+      const { price: { price } } = await program.methods.readPrice(readArgs).accountsPartial(accounts).view();
+      const { price: res } = await program.methods
+        .readPriceWithSlippage(readArgs, price, new anchor.BN(0))
+        .accountsPartial(accounts)
+        .view();
+      expect(res.price.toString()).to.equal(price.toString());
+    });
+
+    it("rejects a price that slipped past the band", async () => {
+      const { price: { price } } = await program.methods.readPrice(readArgs).accountsPartial(accounts).view();
+      // Expecting 2% more than the live price, with 1% tolerance
+      const expected = price.muln(102).divn(100);
+      await expectAnchorError(
+        program.methods
+          .readPriceWithSlippage(readArgs, expected, new anchor.BN(100))
+          .accountsPartial(accounts)
+          .rpc(),
+        "SlippageExceeded"
+      );
+    });
+  });
+
+  describe("read_price_near_ema", () => {
+    const accounts = { priceUpdate: ETH_USD_PRICE_UPDATE, allowlist: allowlistPda };
+
+    // Spot/EMA divergence of the snapshot in bps, rounded down as the program does. Offsets assume
+    // a fully verified PriceUpdateV2 (one-byte verification level).
+    async function snapshotDivergenceBps(): Promise<bigint> {
+      const { data } = (await provider.connection.getAccountInfo(ETH_USD_PRICE_UPDATE))!;
+      const spot = data.readBigInt64LE(73);
+      const ema = data.readBigInt64LE(109);
+      const diff = spot > ema ? spot - ema : ema - spot;
+      return (diff * 10_000n) / (ema < 0n ? -ema : ema);
+    }
+
+    it("passes when spot sits within the band around the EMA", async () => {
+      const bps = await snapshotDivergenceBps();
+      const { price: res } = await program.methods
+        .readPriceNearEma(readArgs, new anchor.BN((bps + 1n).toString()))
+        .accountsPartial(accounts)
+        .view();
+      expect(res.feedId).to.deep.equal(ETH_USD_FEED_ID);
+    });
+
+    it("rejects a spot price that diverged from the EMA", async function () {
+      const bps = await snapshotDivergenceBps();
+      // Bands start at 1 bps, so tripping one needs a snapshot at least 2 bps off its EMA
+      if (bps < 2n) this.skip();
+      await expectAnchorError(
+        program.methods
+          .readPriceNearEma(readArgs, new anchor.BN((bps - 1n).toString()))
+          .accountsPartial(accounts)
+          .rpc(),
+        "SpotEmaDivergence"
+      );
+    });
+  });
+
+  describe("price_age", () => {
+    const accounts = { priceUpdate: ETH_USD_PRICE_UPDATE, allowlist: allowlistPda };
+
+    it("reports the age of a stale snapshot without failing", async () => {
+      const { price: { publishTime } } = await program.methods.readPrice(readArgs).accountsPartial(accounts).view();
+      const age = await program.methods.priceAge(ETH_USD_FEED_ID).accountsPartial(accounts).view();
+      const now = Math.floor(Date.now() / 1000);
+      expect(age.toNumber()).to.be.greaterThan(0);
+      // within a minute of the local clock's view
+      expect(Math.abs(now - publishTime.toNumber() - age.toNumber())).to.be.lessThan(60);
+    });
+
+    it("still rejects a feed the update is not for", async () => {
+      // Allowed, so the rejection comes from the feed check rather than the allowlist
+      const otherFeed = Array.from(Buffer.alloc(32, 7));
+      await program.methods.addFeed(otherFeed).accountsPartial({ admin }).rpc();
+      try {
+        await expectAnchorError(
+          program.methods.priceAge(otherFeed).accountsPartial(accounts).rpc(),
+          "FeedIdMismatch"
+        );
+      } finally {
+        await program.methods.removeFeed(otherFeed).accountsPartial({ admin }).rpc();
+      }
+    });
+  });
+
+  describe("simulate_read", () => {
+    const accounts = { priceUpdate: ETH_USD_PRICE_UPDATE, allowlist: allowlistPda };
+
+    it("reports ok with the price", async () => {
+      const res = await program.methods.simulateRead(readArgs).accountsPartial(accounts).view();
+      expect(res.status).to.deep.equal({ ok: {} });
+      expect(res.price.feedId).to.deep.equal(ETH_USD_FEED_ID);
+    });
+
+    it("reports a stale price instead of failing", async () => {
+      const res = await program.methods
+        .simulateRead({ ...readArgs, maxAgeSecs: new anchor.BN(1) })
+        .accountsPartial(accounts)
+        .view();
+      expect(res.status).to.deep.equal({ stale: {} });
+      expect(res.price).to.not.be.null;
+    });
+
+    it("reports unusable thresholds as bad args rather than a stale price", async () => {
+      const res = await program.methods
+        .simulateRead({ ...readArgs, maxAgeSecs: new anchor.BN(0) })
+        .accountsPartial(accounts)
+        .view();
+      expect(res.status).to.deep.equal({ invalidArgs: {} });
+    });
+
+    it("reports an update published after max_publish_time as too new", async () => {
+      const res = await program.methods
+        .simulateRead({ ...readArgs, maxPublishTime: new anchor.BN(1) })
+        .accountsPartial(accounts)
+        .view();
+      expect(res.status).to.deep.equal({ tooNew: {} });
+    });
+
+    it("reports a feed missing from the update", async () => {
+      const res = await program.methods
+        .simulateRead({ ...readArgs, feedId: Array.from(Buffer.alloc(32, 7)) })
+        .accountsPartial(accounts)
+        .view();
+      expect(res.status).to.deep.equal({ badFeed: {} });
+      expect(res.price).to.be.null;
+    });
+  });
+
+  describe("read_price_batch_same_feed", () => {
+    const update = { pubkey: ETH_USD_PRICE_UPDATE, isWritable: false, isSigner: false };
+
+    it("returns the only update of a single-item batch", async () => {
+      const { price: res } = await program.methods
+        .readPriceBatchSameFeed(readArgs)
+        .remainingAccounts([update])
+        .view();
+      expect(res.feedId).to.deep.equal(ETH_USD_FEED_ID);
+    });
+
+    it("rejects a repeated publish time", async () => {
+      await expectAnchorError(
+        program.methods
+          .readPriceBatchSameFeed(readArgs)
+          .remainingAccounts([update, update])
+          .rpc(),
+        "NonMonotonicPrices"
+      );
+    });
+  });
+
+  describe("cross_price", () => {
+    const crossArgs = {
+      baseFeedId: ETH_USD_FEED_ID,
+      quoteFeedId: ETH_USD_FEED_ID,
+      maxAgeSecs: ANY_AGE,
+      maxConfRatioBps: new anchor.BN(10_000),
+      targetDecimals: 6,
+      rejectNegative: true,
+    };
+    const accounts = { basePriceUpdate: ETH_USD_PRICE_UPDATE, quotePriceUpdate: ETH_USD_PRICE_UPDATE };
+
+    it("returns the ratio through versioned return data", async () => {
+      // A feed priced in itself is exactly 1
+      const { version, rate } = await program.methods.crossPrice(crossArgs).accountsPartial(accounts).view();
+      expect(version).to.equal(1);
+      expect(rate.ratio.toString()).to.equal("1000000");
+      expect(rate.decimals).to.equal(6);
+      expect(rate.quoteFeedId).to.deep.equal(ETH_USD_FEED_ID);
+    });
+
+    it("names a leg the receiver didn't write", async () => {
+      await expectAnchorError(
+        program.methods.crossPrice(crossArgs).accountsPartial({ ...accounts, quotePriceUpdate: configPda }).rpc(),
+        "InvalidPriceAccountOwner"
+      );
+    });
+
+    it("holds both legs to the config", async () => {
+      await program.methods.setMaxAgeOverride(ETH_USD_FEED_ID, new anchor.BN(1)).accountsPartial({ admin }).rpc();
+      try {
+        await expectAnchorError(
+          program.methods.crossPrice(crossArgs).accountsPartial(accounts).rpc(),
+          "PriceTooOld"
+        );
+      } finally {
+        await program.methods.setMaxAgeOverride(ETH_USD_FEED_ID, ANY_AGE).accountsPartial({ admin }).rpc();
+      }
+    });
+  });
+
+  describe("cache", () => {
+    const [cachePda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("cache"), Buffer.from(ETH_USD_FEED_ID)],
+      program.programId
+    );
+    // A one-second window always fails on the cloned snapshot
+    const strictArgs = { ...readArgs, maxAgeSecs: new anchor.BN(1) };
+
+    it("stores the validated observation", async () => {
+      await program.methods
+        .cachePrice(readArgs)
+        .accountsPartial({ priceUpdate: ETH_USD_PRICE_UPDATE, priceCache: cachePda, payer: admin })
+        .rpc();
+
+      const cache = await program.account.priceCache.fetch(cachePda);
+      expect(cache.feedId).to.deep.equal(ETH_USD_FEED_ID);
+      expect(cache.price.toNumber()).to.not.equal(0);
+    });
+
+    it("rejects a price update the receiver didn't write, before touching the cache", async () => {
+      await expectAnchorError(
+        program.methods
+          .cachePrice(readArgs)
+          .accountsPartial({ priceUpdate: configPda, priceCache: cachePda, payer: admin })
+          .rpc(),
+        "InvalidPriceAccountOwner"
+      );
+    });
+
+    it("refuses to write an observation that is not newer than the cached one", async () => {
+      // The snapshot is what the cache already holds, and any older update would be refused the same way
+      const before = await program.account.priceCache.fetch(cachePda);
+      await expectAnchorError(
+        program.methods
+          .cachePrice(readArgs)
+          .accountsPartial({ priceUpdate: ETH_USD_PRICE_UPDATE, priceCache: cachePda, payer: admin })
+          .rpc(),
+        "PriceNotNewerThanCache"
+      );
+      const after = await program.account.priceCache.fetch(cachePda);
+      expect(after.publishTime.toString()).to.equal(before.publishTime.toString());
+    });
+
+    it("creates an empty cache once per feed", async () => {
+      const feedId = Array.from(Buffer.alloc(32, 9));
+      const [pda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cache"), Buffer.from(feedId)],
+        program.programId
+      );
+      await program.methods.initCache(feedId).accountsPartial({ payer: admin }).rpc();
+      const cache = await program.account.priceCache.fetch(pda);
+      expect(cache.feedId).to.deep.equal(feedId);
+      expect(cache.publishTime.toNumber()).to.equal(0);
+
+      // A second init hits the system program's create_account on a live address
+      let err: unknown;
+      try {
+        await program.methods.initCache(feedId).accountsPartial({ payer: admin }).rpc();
+      } catch (e) {
+        err = e;
+      }
+      expect(err, "expected the second init to fail").to.be.instanceOf(anchor.web3.SendTransactionError);
+      const logs = (err as anchor.web3.SendTransactionError).logs ?? [];
+      expect(logs.some((l) => l.includes("already in use"))).to.equal(true);
+    });
+
+    describe("cache_prices", () => {
+      const update = { pubkey: ETH_USD_PRICE_UPDATE, isWritable: false, isSigner: false };
+      const cache = { pubkey: cachePda, isWritable: true, isSigner: false };
+      const cachePrices = (maxAgeSecs: anchor.BN, pairs: (typeof update)[][], writer = admin) =>
+        program.methods
+          .cachePrices({
+            feedIds: new Array(pairs.length).fill(ETH_USD_FEED_ID),
+            maxAgeSecs,
+            maxConfRatioBps: new anchor.BN(10_000),
+            rejectNegative: true,
+          })
+          .accountsPartial({ writer })
+          .remainingAccounts(pairs.flat());
+
+      before(async () => {
+        // The snapshot is already cached above, so start again from an empty ETH/USD cache
+        await program.methods.closeCache().accountsPartial({ priceCache: cachePda, receiver: admin, admin }).rpc();
+        await program.methods.initCache(ETH_USD_FEED_ID).accountsPartial({ payer: admin }).rpc();
+      });
+
+      it("writes every fresh feed and reports it in the mask", async () => {
+        // The second pair repeats the observation the first one just wrote, so only bit 0 is set
+        const mask = await cachePrices(ANY_AGE, [[update, cache], [update, cache]]).view();
+        expect(mask).to.equal(0b01);
+        await cachePrices(ANY_AGE, [[update, cache]]).rpc();
+        const cached = await program.account.priceCache.fetch(cachePda);
+        expect(cached.publishTime.toNumber()).to.be.greaterThan(0);
+      });
+
+      it("skips an update that is not newer than the cache, leaving it as is", async () => {
+        const before = await program.account.priceCache.fetch(cachePda);
+        expect(await cachePrices(ANY_AGE, [[update, cache]]).view()).to.equal(0);
+        await cachePrices(ANY_AGE, [[update, cache]]).rpc();
+        const after = await program.account.priceCache.fetch(cachePda);
+        expect(after.publishTime.toString()).to.equal(before.publishTime.toString());
+        expect(after.price.toString()).to.equal(before.price.toString());
+      });
+
+      it("skips a stale feed without failing the batch", async () => {
+        const before = await program.account.priceCache.fetch(cachePda);
+        const mask = await cachePrices(new anchor.BN(1), [[update, cache]]).view();
+        expect(mask).to.equal(0);
+        await cachePrices(new anchor.BN(1), [[update, cache]]).rpc();
+        const after = await program.account.priceCache.fetch(cachePda);
+        expect(after.publishTime.toNumber()).to.equal(before.publishTime.toNumber());
+      });
+
+      it("rejects a cache that belongs to another feed", async () => {
+        const [otherCache] = PublicKey.findProgramAddressSync(
+          [Buffer.from("cache"), Buffer.alloc(32, 9)],
+          program.programId
+        );
+        await expectAnchorError(
+          cachePrices(ANY_AGE, [[update, { ...cache, pubkey: otherCache }]]).rpc(),
+          "ConstraintSeeds"
+        );
+      });
+
+      it("refuses every writer but the config's cache writer once one is set", async () => {
+        const keeper = Keypair.generate();
+        await program.methods.setCacheWriter(keeper.publicKey).accountsPartial({ admin }).rpc();
+        try {
+          // Neither the permissionless single write nor the keeper batch accepts anyone else, admin included
+          await expectAnchorError(
+            program.methods
+              .cachePrice(readArgs)
+              .accountsPartial({ priceUpdate: ETH_USD_PRICE_UPDATE, priceCache: cachePda, payer: admin })
+              .rpc(),
+            "UnauthorizedCacheWriter"
+          );
+          await expectAnchorError(cachePrices(ANY_AGE, [[update, cache]]).rpc(), "UnauthorizedCacheWriter");
+
+          // The keeper gets past the gate; the cache already holds the snapshot, so nothing is written
+          const mask = await cachePrices(ANY_AGE, [[update, cache]], keeper.publicKey)
+            .signers([keeper])
+            .view();
+          expect(mask).to.equal(0);
+        } finally {
+          await program.methods.setCacheWriter(null).accountsPartial({ admin }).rpc();
+        }
+      });
+    });
+
+    it("lets the admin read and cache in one signed call", async () => {
+      // A one-second window fails on the snapshot, so only check that the admin gets past the
+      // signer check
+      await expectAnchorError(
+        program.methods
+          .readPriceSignedByAuthority(strictArgs)
+          .accountsPartial({ priceUpdate: ETH_USD_PRICE_UPDATE, priceCache: cachePda, admin })
+          .rpc(),
+        "PriceTooOld"
+      );
+    });
+
+    it("rejects a signed read from a non-admin", async () => {
+      const intruder = Keypair.generate();
+      await expectAnchorError(
+        program.methods
+          .readPriceSignedByAuthority(readArgs)
+          .accountsPartial({ priceUpdate: ETH_USD_PRICE_UPDATE, priceCache: cachePda, admin: intruder.publicKey })
+          .signers([intruder])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+
+    it("falls back to the cached price when opted in", async () => {
+      const { read: res } = await program.methods
+        .readPriceOrCached(strictArgs, true)
+        .accountsPartial({ priceUpdate: ETH_USD_PRICE_UPDATE, priceCache: cachePda, allowlist: allowlistPda })
+        .view();
+
+      const cache = await program.account.priceCache.fetch(cachePda);
+      expect(res.stale).to.equal(true);
+      expect(res.price.price.toString()).to.equal(cache.price.toString());
+    });
+
+    it("lets read_price through when it matches the cached price", async () => {
+      // The snapshot never moves, so even a 0.01% band holds
+      await program.methods
+        .readPrice({ ...readArgs, maxDeviationBps: new anchor.BN(1) })
+        .accountsPartial({
+          priceUpdate: ETH_USD_PRICE_UPDATE,
+          allowlist: allowlistPda,
+          priceCache: cachePda,
+        })
+        .rpc();
+    });
+
+    it("refuses a deviation limit without the cache account to compare against", async () => {
+      await expectAnchorError(
+        program.methods
+          .readPrice({ ...readArgs, maxDeviationBps: new anchor.BN(1) })
+          .accountsPartial({ priceUpdate: ETH_USD_PRICE_UPDATE, allowlist: allowlistPda })
+          .rpc(),
+        "MissingPriceCache"
+      );
+    });
+
+    it("refuses a deviation limit on a read that takes no cache, instead of ignoring it", async () => {
+      await expectAnchorError(
+        program.methods
+          .readEmaPrice({ ...readArgs, maxDeviationBps: new anchor.BN(1) })
+          .accountsPartial({ priceUpdate: ETH_USD_PRICE_UPDATE, allowlist: allowlistPda })
+          .rpc(),
+        "InvalidArgs"
+      );
+    });
+
+    it("keeps strict behavior when not opted in", async () => {
+      await expectAnchorError(
+        program.methods
+          .readPriceOrCached(strictArgs, false)
+          .accountsPartial({ priceUpdate: ETH_USD_PRICE_UPDATE, priceCache: cachePda, allowlist: allowlistPda })
+          .rpc(),
+        "PriceTooOld"
+      );
+    });
+
+    describe("close_cache", () => {
+      // The empty cache from the init test above, so the ETH cache stays for the other tests
+      const [spareCache] = PublicKey.findProgramAddressSync(
+        [Buffer.from("cache"), Buffer.alloc(32, 9)],
+        program.programId
+      );
+
+      it("rejects a non-admin", async () => {
+        const intruder = Keypair.generate();
+        await expectAnchorError(
+          program.methods
+            .closeCache()
+            .accountsPartial({ priceCache: spareCache, receiver: intruder.publicKey, admin: intruder.publicKey })
+            .signers([intruder])
+            .rpc(),
+          "Unauthorized"
+        );
+      });
+
+      it("refunds the rent and clears the account", async () => {
+        const receiver = Keypair.generate().publicKey;
+        const rent = await provider.connection.getBalance(spareCache);
+        expect(rent).to.be.greaterThan(0);
+
+        await program.methods
+          .closeCache()
+          .accountsPartial({ priceCache: spareCache, receiver, admin })
+          .rpc();
 
-  it("Is initialized!", async () => {
-    // Add your test here.
-    const tx = await program.methods.initialize().rpc();
-    console.log("Your transaction signature", tx);
+        expect(await provider.connection.getBalance(receiver)).to.equal(rent);
+        expect(await provider.connection.getAccountInfo(spareCache)).to.equal(null);
+      });
+    });
   });
 });